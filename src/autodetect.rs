@@ -0,0 +1,192 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serialport::{DataBits, Parity};
+
+use crate::config::Config;
+use crate::framing::Framing;
+use crate::protocol::{ScaleCommand, ScaleProtocol};
+
+const BAUD_RATES: &[u32] = &[9600, 4800, 19200, 2400, 38400, 1200, 57600, 115200];
+const FORMATOS: &[(DataBits, Parity)] = &[
+    (DataBits::Eight, Parity::None),
+    (DataBits::Seven, Parity::None),
+    (DataBits::Seven, Parity::Even),
+    (DataBits::Seven, Parity::Odd),
+    (DataBits::Eight, Parity::Even),
+    (DataBits::Eight, Parity::Odd),
+];
+
+/// Cada cuánto se reenvía la solicitud de peso durante la prueba (básculas por demanda).
+const INTERVALO_POLL: Duration = Duration::from_millis(300);
+
+/// Resultado de escuchar el puerto con una combinación de parámetros.
+#[derive(Debug, Clone)]
+pub struct Candidato {
+    pub baud_rate: u32,
+    pub data_bits: DataBits,
+    pub parity: Parity,
+    pub bytes: usize,
+    pub imprimibles: f64,
+    pub tramas: usize,
+    pub interpretadas: usize,
+}
+
+impl Candidato {
+    pub fn puntaje(&self) -> f64 {
+        if self.bytes == 0 {
+            return 0.0;
+        }
+        self.imprimibles * 10.0 + self.tramas.min(20) as f64 + 3.0 * self.interpretadas.min(20) as f64
+    }
+}
+
+/// Recorre las combinaciones habituales de baud rate, bits de datos y
+/// paridad, escucha el puerto durante `deteccion_ms` en cada una y
+/// devuelve los candidatos ordenados del mejor al peor.
+pub fn detectar(config: &Config, protocol: &dyn ScaleProtocol) -> Result<Vec<Candidato>> {
+    let mut candidatos = Vec::new();
+    let duracion = Duration::from_millis(config.deteccion_ms);
+
+    info!(
+        "🔍 Detectando parámetros en {} ({} combinaciones, {} ms cada una)...",
+        config.serial_port,
+        BAUD_RATES.len() * FORMATOS.len(),
+        config.deteccion_ms
+    );
+
+    for &baud_rate in BAUD_RATES {
+        for &(data_bits, parity) in FORMATOS {
+            let mut prueba = config.clone();
+            prueba.baud_rate = baud_rate;
+            prueba.data_bits = data_bits;
+            prueba.parity = parity;
+
+            let datos = match escuchar(&prueba, protocol, duracion) {
+                Ok(datos) => datos,
+                Err(e) => {
+                    warn!("⚠️ {}", e);
+                    continue;
+                }
+            };
+
+            let candidato = evaluar(&prueba, protocol, &datos);
+            info!(
+                "  {:>6} {:?}/{:?}: {} bytes, {:.0}% imprimibles, {} tramas, {} interpretadas → {:.1}",
+                baud_rate,
+                data_bits,
+                parity,
+                candidato.bytes,
+                candidato.imprimibles * 100.0,
+                candidato.tramas,
+                candidato.interpretadas,
+                candidato.puntaje()
+            );
+            candidatos.push(candidato);
+        }
+    }
+
+    candidatos.sort_by(|a, b| b.puntaje().total_cmp(&a.puntaje()));
+    Ok(candidatos)
+}
+
+fn escuchar(config: &Config, protocol: &dyn ScaleProtocol, duracion: Duration) -> Result<Vec<u8>> {
+    let mut serial = config.open_serial_port()?;
+    let poll = protocol.command(ScaleCommand::Poll);
+    let mut datos = Vec::new();
+    let mut buffer = [0u8; 1024];
+    let inicio = Instant::now();
+    let mut ultimo_poll: Option<Instant> = None;
+
+    while inicio.elapsed() < duracion {
+        if let Some(bytes) = &poll {
+            if ultimo_poll.map(|t| t.elapsed() >= INTERVALO_POLL).unwrap_or(true) {
+                let _ = serial.write_all(bytes).and_then(|_| serial.flush());
+                ultimo_poll = Some(Instant::now());
+            }
+        }
+
+        match serial.read(&mut buffer) {
+            Ok(n) if n > 0 => datos.extend_from_slice(&buffer[..n]),
+            _ => thread::sleep(Duration::from_millis(20)),
+        }
+    }
+
+    Ok(datos)
+}
+
+fn evaluar(config: &Config, protocol: &dyn ScaleProtocol, datos: &[u8]) -> Candidato {
+    let imprimibles = datos
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || matches!(**b, b' ' | b'\r' | b'\n' | 0x02 | 0x03))
+        .count();
+
+    let mut pendiente = datos.to_vec();
+    let (mut tramas, mut interpretadas) = (0, 0);
+    // IdleGap no aplica a datos ya acumulados: se usa el framing del protocolo
+    let framing = config.framing.as_ref().filter(|f| !f.depende_del_tiempo());
+    while let Some(trama) = siguiente(framing, protocol, &mut pendiente) {
+        tramas += 1;
+        if protocol.parse(&trama).is_some() {
+            interpretadas += 1;
+        }
+    }
+
+    Candidato {
+        baud_rate: config.baud_rate,
+        data_bits: config.data_bits,
+        parity: config.parity,
+        bytes: datos.len(),
+        imprimibles: if datos.is_empty() { 0.0 } else { imprimibles as f64 / datos.len() as f64 },
+        tramas,
+        interpretadas,
+    }
+}
+
+fn siguiente(framing: Option<&Framing>, protocol: &dyn ScaleProtocol, pendiente: &mut Vec<u8>) -> Option<Vec<u8>> {
+    match framing {
+        Some(f) => f.next_frame(pendiente, None),
+        None => protocol.next_frame(pendiente),
+    }
+}
+
+static RE_BAUD: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^baud_rate\s*=.*$").unwrap());
+static RE_DATA_BITS: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^data_bits\s*=.*$").unwrap());
+static RE_PARITY: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?m)^parity\s*=.*$").unwrap());
+
+/// Escribe en el archivo de configuración los parámetros del candidato,
+/// conservando el resto del contenido (comentarios incluidos).
+pub fn guardar(path: &str, candidato: &Candidato) -> Result<()> {
+    let contenido = fs::read_to_string(path)
+        .with_context(|| format!("Error leyendo archivo de configuración {}", path))?;
+
+    let data_bits = match candidato.data_bits {
+        DataBits::Five => "5",
+        DataBits::Six => "6",
+        DataBits::Seven => "7",
+        DataBits::Eight => "8",
+    };
+    let parity = match candidato.parity {
+        Parity::None => "None",
+        Parity::Odd => "Odd",
+        Parity::Even => "Even",
+    };
+
+    if !RE_BAUD.is_match(&contenido) || !RE_DATA_BITS.is_match(&contenido) || !RE_PARITY.is_match(&contenido) {
+        bail!("El archivo {} no tiene las claves baud_rate/data_bits/parity", path);
+    }
+
+    let contenido = RE_BAUD.replace(&contenido, format!("baud_rate = {}", candidato.baud_rate));
+    let contenido = RE_DATA_BITS.replace(&contenido, format!("data_bits = \"{}\"", data_bits));
+    let contenido = RE_PARITY.replace(&contenido, format!("parity = \"{}\"", parity));
+
+    fs::write(path, contenido.as_ref())
+        .with_context(|| format!("Error escribiendo archivo de configuración {}", path))?;
+    Ok(())
+}
@@ -1,29 +1,135 @@
-use parking_lot::Mutex;
+use chrono::{DateTime, Local};
+use flume::{unbounded, Receiver, Sender};
+use parking_lot::{Condvar, Mutex, MutexGuard};
 use std::sync::Arc;
-use std::time::{Instant};
+use std::time::Instant;
 
-use crate::serial_utils::sanitize_log_data;
+use crate::protocol::ScaleResponse;
+use crate::weight::WeightReading;
 
-pub type SharedCache = Arc<Mutex<Cache>>;
+/// Caché compartida entre el lector serial y los clientes. Cada trama o
+/// respuesta nueva incrementa la versión y despierta a quienes esperan.
+#[derive(Clone, Default)]
+pub struct SharedCache {
+    inner: Arc<Compartida>,
+}
+
+#[derive(Default)]
+struct Compartida {
+    cache: Mutex<Cache>,
+    cambio: Condvar,
+}
+
+impl SharedCache {
+    pub fn lock(&self) -> MutexGuard<'_, Cache> {
+        self.inner.cache.lock()
+    }
+
+    /// Guarda una trama nueva y notifica a los que esperan
+    pub fn set(&self, data: Vec<u8>, reading: Option<WeightReading>) {
+        self.lock().set(data, reading);
+        self.inner.cambio.notify_all();
+    }
+
+    /// Guarda una respuesta de la báscula y notifica a los que esperan
+    pub fn set_response(&self, response: ScaleResponse) {
+        self.lock().set_response(response);
+        self.inner.cambio.notify_all();
+    }
+
+    /// Bloquea hasta que la versión supere `version` o se alcance `limite`;
+    /// devuelve la caché bloqueada en cualquiera de los dos casos.
+    pub fn esperar_cambio(&self, version: u64, limite: Instant) -> MutexGuard<'_, Cache> {
+        let mut guard = self.lock();
+        while guard.version() <= version {
+            if self.inner.cambio.wait_until(&mut guard, limite).timed_out() {
+                break;
+            }
+        }
+        guard
+    }
+}
+
+/// Trama guardada en la caché y enviada a los suscriptores: bytes crudos,
+/// lectura interpretada y momento de llegada.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trama {
+    pub data: Vec<u8>,
+    pub lectura: Option<WeightReading>,
+    pub recibida: Instant,
+    /// Fecha y hora de llegada, para informar a los clientes
+    pub fecha: DateTime<Local>,
+}
 
 pub struct Cache {
-    data: Option<(Vec<u8>, Instant)>,
+    data: Option<Trama>,
+    response: Option<(ScaleResponse, Instant)>,
+    suscriptores: Vec<Sender<Trama>>,
+    /// Se incrementa con cada trama o respuesta nueva
+    version: u64,
+    /// Cantidad de tramas guardadas desde el inicio
+    secuencia: u64,
 }
 
 impl Cache {
     /// Crea una nueva instancia vacía
     pub fn new() -> Self {
-        Self { data: None }
+        Self { data: None, response: None, suscriptores: Vec::new(), version: 0, secuencia: 0 }
     }
 
-    /// Establece nuevos datos con su timestamp
-    pub fn set(&mut self, data: Vec<u8>) {
-        self.data = Some((data, Instant::now()));
+    /// Establece nuevos datos, junto con su lectura interpretada, con su timestamp,
+    /// y lo reenvía a los suscriptores (descartando los que se desconectaron)
+    fn set(&mut self, data: Vec<u8>, reading: Option<WeightReading>) {
+        let trama = Trama { data, lectura: reading, recibida: Instant::now(), fecha: Local::now() };
+        if !self.suscriptores.is_empty() {
+            self.suscriptores.retain(|tx| tx.send(trama.clone()).is_ok());
+        }
+        self.data = Some(trama);
+        self.version += 1;
+        self.secuencia += 1;
+    }
+
+    /// Registra un suscriptor que recibirá cada nueva trama
+    pub fn suscribir(&mut self) -> Receiver<Trama> {
+        let (tx, rx) = unbounded();
+        self.suscriptores.push(tx);
+        rx
+    }
+
+    /// Cantidad de suscriptores activos
+    pub fn suscriptores(&self) -> usize {
+        self.suscriptores.len()
     }
 
     /// Permite acceder a los datos y su timestamp (uso interno controlado)
     pub fn get_raw(&self) -> Option<(&[u8], Instant)> {
-        self.data.as_ref().map(|(d, t)| (d.as_slice(), *t))
+        self.data.as_ref().map(|t| (t.data.as_slice(), t.recibida))
+    }
+
+    /// Última trama completa (bytes, lectura y fecha de llegada)
+    pub fn get_trama(&self) -> Option<&Trama> {
+        self.data.as_ref()
+    }
+
+    /// Registra la última respuesta de la báscula a un comando
+    fn set_response(&mut self, response: ScaleResponse) {
+        self.response = Some((response, Instant::now()));
+        self.version += 1;
+    }
+
+    /// Versión actual: cambia con cada trama o respuesta registrada
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Cantidad de tramas guardadas desde el inicio
+    pub fn secuencia(&self) -> u64 {
+        self.secuencia
+    }
+
+    /// Última respuesta de la báscula a un comando y su timestamp
+    pub fn get_response(&self) -> Option<(&ScaleResponse, Instant)> {
+        self.response.as_ref().map(|(r, t)| (r, *t))
     }
 
 }
@@ -33,4 +139,3 @@ impl Default for Cache {
         Self::new()
     }
 }
-
@@ -1,25 +1,252 @@
 // === src/command.rs ===
-use once_cell::sync::Lazy;
-use regex::Regex;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum Comando {
-    Uno,
-    W,
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::poll::CriterioEstable;
+use crate::protocol::{ScaleCommand, ScaleProtocol};
+
+/// Entrada de la tabla `[commands]`: qué hace un comando TCP.
+/// Los campos omitidos toman los valores de las claves generales
+/// (`cache_duration_ms`, `cache_wait_ms`, `w_duration_ms`, `w_response_timeout_ms`) y del protocolo.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CommandSpec {
+    /// Comando de báscula; si falta, solo se responde desde la caché
+    #[serde(default)]
+    pub scale: Option<ScaleCommand>,
+    /// Bytes a enviar por serial; si faltan se usan los del protocolo
+    #[serde(default)]
+    pub serial: Option<String>,
+    /// Tiempo de espera de la respuesta de la báscula
+    #[serde(default)]
+    pub wait_ms: Option<u64>,
+    /// Antigüedad máxima aceptada del dato en caché
+    #[serde(default)]
+    pub cache_ms: Option<u64>,
+}
+
+impl CommandSpec {
+    fn de(scale: Option<ScaleCommand>) -> Self {
+        Self { scale, serial: None, wait_ms: None, cache_ms: None }
+    }
+
+    /// Esta entrada completada con los campos de `base` donde falten.
+    pub fn sobre(&self, base: &CommandSpec) -> CommandSpec {
+        CommandSpec {
+            scale: self.scale.or(base.scale),
+            serial: self.serial.clone().or_else(|| base.serial.clone()),
+            wait_ms: self.wait_ms.or(base.wait_ms),
+            cache_ms: self.cache_ms.or(base.cache_ms),
+        }
+    }
 }
 
-static RE_CMD_1: Lazy<Regex> = Lazy::new(|| Regex::new(r"^1+\s*$").unwrap());
-static RE_CMD_W: Lazy<Regex> = Lazy::new(|| Regex::new(r"^W+\s*$").unwrap());
+/// Tabla por defecto: los comandos históricos `1`, `W` y `S` (con su alias
+/// `STABLE`) más los de operación.
+pub fn default_commands() -> BTreeMap<String, CommandSpec> {
+    [
+        ("1", None),
+        ("W", Some(ScaleCommand::Poll)),
+        ("S", Some(ScaleCommand::StablePoll)),
+        ("STABLE", Some(ScaleCommand::StablePoll)),
+        ("TARE", Some(ScaleCommand::Tare)),
+        ("ZERO", Some(ScaleCommand::Zero)),
+        ("PRINT", Some(ScaleCommand::Print)),
+        ("CLEAR", Some(ScaleCommand::Clear)),
+        ("GROSSNET", Some(ScaleCommand::GrossNet)),
+        ("GN", Some(ScaleCommand::GrossNet)),
+    ]
+    .into_iter()
+    .map(|(nombre, scale)| (nombre.to_string(), CommandSpec::de(scale)))
+    .collect()
+}
+
+/// Comando TCP reconocido, con sus parámetros ya resueltos.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Comando {
+    pub nombre: String,
+    pub scale: Option<ScaleCommand>,
+    /// Bytes explícitos de la tabla; `None` usa los del protocolo
+    pub serial: Option<Vec<u8>>,
+    pub espera: Duration,
+    pub frescura: Duration,
+    /// Criterio de peso estable para `stable_poll` en tramas sin indicador de movimiento
+    pub criterio: CriterioEstable,
+}
 
 impl Comando {
-    pub fn parse(input: &str) -> Option<Self> {
-        if RE_CMD_1.is_match(input) {
-            Some(Comando::Uno)
-        } else if RE_CMD_W.is_match(input) {
-            Some(Comando::W)
-        } else {
-            None
+    /// Busca la línea en la tabla `[commands]` (sin distinguir mayúsculas).
+    /// Los nombres de un solo carácter aceptan repeticiones (`111`, `WW`).
+    /// Un número opcional después del nombre reemplaza el tiempo de espera (`1 500`).
+    pub fn parse(input: &str, config: &Config) -> Option<Self> {
+        let mut palabras = input.split_whitespace();
+        let linea = palabras.next()?.to_uppercase();
+        let espera_pedida = match palabras.next() {
+            Some(arg) => Some(arg.parse::<u64>().ok()?),
+            None => None,
+        };
+        if palabras.next().is_some() {
+            return None;
+        }
+
+        let (nombre, spec) = config.commands.iter().find(|(nombre, _)| {
+            let nombre = nombre.to_uppercase();
+            linea == nombre || (nombre.chars().count() == 1 && !linea.is_empty() && linea.chars().all(|c| nombre.starts_with(c)))
+        })?;
+
+        let (frescura_defecto, espera_defecto) = match spec.scale {
+            None => (config.cache_duration_ms, config.cache_wait_ms),
+            Some(ScaleCommand::StablePoll) => (config.w_duration_ms, config.stable_timeout_ms),
+            Some(_) => (config.w_duration_ms, config.w_response_timeout_ms),
+        };
+
+        Some(Comando {
+            nombre: nombre.clone(),
+            scale: spec.scale,
+            serial: spec.serial.as_ref().map(|s| s.as_bytes().to_vec()),
+            espera: Duration::from_millis(espera_pedida.or(spec.wait_ms).unwrap_or(espera_defecto)),
+            frescura: Duration::from_millis(spec.cache_ms.unwrap_or(frescura_defecto)),
+            criterio: CriterioEstable { lecturas: config.stable_readings, tolerancia: config.stable_tolerance },
+        })
+    }
+
+    /// Bytes a enviar a la báscula: los de la tabla o los del protocolo.
+    pub fn bytes(&self, protocol: &dyn ScaleProtocol) -> Option<Vec<u8>> {
+        self.serial.clone().or_else(|| protocol.command(self.scale?))
+    }
+}
+
+/// Resultado de un comando reenviado a la báscula, tal como se informa al cliente.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resultado {
+    /// La báscula confirmó el comando
+    Ok,
+    /// La báscula rechazó el comando, con su código de error
+    Error(String),
+    /// La báscula suele confirmar este comando pero no respondió a tiempo
+    SinConfirmacion,
+    /// El protocolo no tiene secuencia para este comando
+    NoSoportado,
+    /// Enviado a una báscula que no confirma comandos
+    Enviado,
+}
+
+
+/// Largo máximo de una línea de comando; lo que exceda se descarta hasta el próximo terminador.
+pub const MAX_LINEA: usize = 256;
+
+/// Resultado de extraer una línea del buffer de la conexión.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Linea {
+    Texto(String),
+    /// La línea superó `MAX_LINEA` y fue descartada
+    Excedida,
+}
+
+/// Acumula los bytes de una conexión TCP y los separa en líneas terminadas
+/// en CR, LF o CRLF. Las líneas vacías se ignoran.
+#[derive(Default)]
+pub struct LectorLineas {
+    pendiente: Vec<u8>,
+    /// Se está descartando una línea demasiado larga hasta su terminador
+    descartando: bool,
+}
+
+impl LectorLineas {
+    pub fn agregar(&mut self, bytes: &[u8]) {
+        self.pendiente.extend_from_slice(bytes);
+    }
+
+    /// Devuelve la próxima línea completa, si la hay.
+    pub fn siguiente(&mut self) -> Option<Linea> {
+        loop {
+            let fin = match self.pendiente.iter().position(|b| *b == b'\r' || *b == b'\n') {
+                Some(fin) => fin,
+                None => {
+                    if self.pendiente.len() > MAX_LINEA {
+                        self.pendiente.clear();
+                        if !self.descartando {
+                            self.descartando = true;
+                            return Some(Linea::Excedida);
+                        }
+                    }
+                    return None;
+                }
+            };
+
+            let linea: Vec<u8> = self.pendiente.drain(..=fin).collect();
+            if self.descartando {
+                self.descartando = false;
+                continue;
+            }
+            if linea.len() - 1 > MAX_LINEA {
+                return Some(Linea::Excedida);
+            }
+
+            let texto = String::from_utf8_lossy(&linea[..fin]).trim().to_string();
+            if !texto.is_empty() {
+                return Some(Linea::Texto(texto));
+            }
         }
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texto(s: &str) -> Option<Linea> {
+        Some(Linea::Texto(s.to_string()))
+    }
+
+    #[test]
+    fn crlf_partido_entre_lecturas() {
+        let mut lector = LectorLineas::default();
+        lector.agregar(b"W");
+        assert_eq!(lector.siguiente(), None);
+        lector.agregar(b"\r");
+        assert_eq!(lector.siguiente(), texto("W"));
+        // El LF que completa el CRLF no genera una línea vacía
+        lector.agregar(b"\n");
+        assert_eq!(lector.siguiente(), None);
+    }
+
+    #[test]
+    fn lineas_encadenadas() {
+        let mut lector = LectorLineas::default();
+        lector.agregar(b"W\r\n1\r\n");
+        assert_eq!(lector.siguiente(), texto("W"));
+        assert_eq!(lector.siguiente(), texto("1"));
+        assert_eq!(lector.siguiente(), None);
+    }
+
+    #[test]
+    fn lineas_vacias() {
+        let mut lector = LectorLineas::default();
+        lector.agregar(b"\r\n\n  \r\nTARE\n");
+        assert_eq!(lector.siguiente(), texto("TARE"));
+        assert_eq!(lector.siguiente(), None);
+    }
+
+    #[test]
+    fn linea_demasiado_larga() {
+        // Sin terminador: se informa una vez y se descarta hasta el próximo fin de línea
+        let mut lector = LectorLineas::default();
+        lector.agregar(&[b'x'; MAX_LINEA + 1]);
+        assert_eq!(lector.siguiente(), Some(Linea::Excedida));
+        lector.agregar(&[b'x'; MAX_LINEA + 1]);
+        assert_eq!(lector.siguiente(), None);
+        lector.agregar(b"xx\r\nW\r\n");
+        assert_eq!(lector.siguiente(), texto("W"));
+
+        // Con terminador en la misma lectura
+        let mut larga = vec![b'x'; MAX_LINEA + 1];
+        larga.extend_from_slice(b"\r\n1\r\n");
+        lector.agregar(&larga);
+        assert_eq!(lector.siguiente(), Some(Linea::Excedida));
+        assert_eq!(lector.siguiente(), texto("1"));
+        assert_eq!(lector.siguiente(), None);
+    }
+}
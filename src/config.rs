@@ -1,4 +1,4 @@
-use std::{fs, io::Write, sync::Arc, thread, time::Duration};
+use std::{collections::BTreeMap, fs, io::Write, sync::Arc, thread, time::Duration};
 
 use anyhow::{Context, Result};
 use log::info;
@@ -6,6 +6,17 @@ use parking_lot::RwLock;
 use serialport::{DataBits, Parity, SerialPort, StopBits};
 use serde::Deserialize;
 
+use crate::command::CommandSpec;
+use crate::filter::{FilterRule, Filtro};
+use crate::framing::Framing;
+use crate::integrity::Integrity;
+use crate::modbus::ModbusConfig;
+use crate::mqtt::MqttConfig;
+use crate::plantilla::Plantillas;
+use crate::poll::Coordinador;
+use crate::protocol::ScaleProtocol;
+use crate::serial_utils::sanitize_log_data;
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub serial_port: String,
@@ -20,14 +31,83 @@ pub struct Config {
     pub timeout_ms: u64,
     #[serde(default = "default_cache_duration_ms")]
     pub cache_duration_ms: u64,
+    /// Cuánto espera `1` una trama nueva si la caché está vencida (0 = no espera)
+    #[serde(default)]
+    pub cache_wait_ms: u64,
     #[serde(default = "default_w_duration_ms")]
     pub w_duration_ms: u64,
     #[serde(default = "default_w_response_timeout_ms")]
     pub w_response_timeout_ms: u64,
     #[serde(default = "default_tcp_address")]
     pub tcp_address: String,
+    /// Puertos TCP adicionales, cada uno con sus propias plantillas
+    #[serde(default, rename = "listener")]
+    pub listeners: Vec<Listener>,
+    /// Dirección del servidor HTTP (API REST); si falta no se inicia
+    #[serde(default)]
+    pub http_address: Option<String>,
+    /// Dirección del servidor WebSocket (pesos en vivo); si falta no se inicia
+    #[serde(default)]
+    pub websocket_address: Option<String>,
+    /// Publicación en un broker MQTT; si falta no se conecta
+    #[serde(default)]
+    pub mqtt: Option<MqttConfig>,
+    /// Esclavo Modbus TCP; si falta no se inicia
+    #[serde(default)]
+    pub modbus: Option<ModbusConfig>,
+    /// Sin tramas durante este tiempo la báscula se informa fuera de línea
+    #[serde(default = "default_offline_after_ms")]
+    pub offline_after_ms: u64,
+    /// Plantillas de respuesta de texto de todos los puertos
+    #[serde(default)]
+    pub responses: Plantillas,
     #[serde(default = "default_recargar_configuracion")]
     pub recargar_configuracion: bool,
+    #[serde(default = "default_protocol")]
+    pub protocol: String,
+    /// Identificador de la báscula informado en las respuestas JSON
+    #[serde(default = "default_scale_id")]
+    pub scale_id: String,
+    #[serde(default)]
+    pub toledo_checksum: bool,
+    /// Framing explícito; si falta se usa el del protocolo
+    #[serde(default)]
+    pub framing: Option<Framing>,
+    /// Reglas de descarte/aceptación de tramas, evaluadas en orden;
+    /// si faltan se usan las del protocolo
+    #[serde(default, rename = "filter")]
+    pub filters: Option<Vec<FilterRule>>,
+    /// Validación de integridad explícita; si falta se usa la del protocolo
+    #[serde(default)]
+    pub integrity: Option<Integrity>,
+    /// Tabla de comandos TCP; las entradas se suman a las por defecto
+    #[serde(default = "crate::command::default_commands", deserialize_with = "crate::serial_utils::deserialize_commands")]
+    pub commands: BTreeMap<String, CommandSpec>,
+    /// Tiempo de espera por defecto de `STABLE`
+    #[serde(default = "default_stable_timeout_ms")]
+    pub stable_timeout_ms: u64,
+    /// Lecturas iguales consecutivas que cuentan como peso estable
+    /// (protocolos sin indicador de movimiento)
+    #[serde(default = "default_stable_readings")]
+    pub stable_readings: usize,
+    /// Diferencia máxima entre esas lecturas
+    #[serde(default)]
+    pub stable_tolerance: f64,
+    /// Tiempo mínimo entre dos solicitudes de peso enviadas a la báscula
+    #[serde(default)]
+    pub min_poll_interval_ms: u64,
+    /// Tiempo de escucha por combinación en el modo `--detectar`
+    #[serde(default = "default_deteccion_ms")]
+    pub deteccion_ms: u64,
+}
+
+/// Entrada `[[listener]]`: otra dirección donde escucha el servidor TCP.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct Listener {
+    pub address: String,
+    /// Plantillas propias; las omitidas se toman de `[responses]`
+    #[serde(default)]
+    pub responses: Plantillas,
 }
 
 fn default_timeout_ms() -> u64 { 1000 }
@@ -36,6 +116,12 @@ fn default_w_duration_ms() -> u64 { 500 }
 fn default_w_response_timeout_ms() -> u64 { 500 }
 fn default_tcp_address() -> String { "0.0.0.0:2029".to_string() }
 fn default_recargar_configuracion() -> bool { true }
+fn default_protocol() -> String { "generic".to_string() }
+fn default_scale_id() -> String { "balanza".to_string() }
+fn default_offline_after_ms() -> u64 { 5000 }
+fn default_deteccion_ms() -> u64 { 1500 }
+fn default_stable_timeout_ms() -> u64 { 3000 }
+fn default_stable_readings() -> usize { 3 }
 
 impl Config {
     pub fn load_from_file(path: &str) -> Result<Self> {
@@ -43,6 +129,10 @@ impl Config {
             .with_context(|| format!("Error leyendo archivo de configuración {}", path))?;
         let config: Config = toml::from_str(&content)
             .with_context(|| "Error parseando archivo TOML con serde")?;
+        // Validar las reglas (regex) antes de aceptar la configuración
+        if let Some(reglas) = &config.filters {
+            Filtro::compilar(reglas, Default::default())?;
+        }
         Ok(config)
     }
 
@@ -69,18 +159,79 @@ impl Config {
         info!("  Stop bits             : {:?}", self.stop_bits);
         info!("  Timeout (ms)          : {}", self.timeout_ms);
         info!("  Cache duration (ms)   : {}", self.cache_duration_ms);
+        info!("  Espera de caché (ms)  : {}", self.cache_wait_ms);
         info!("  W duración (ms)       : {}", self.w_duration_ms);
         info!("  W respuesta timeout   : {}", self.w_response_timeout_ms);
         info!("  Dirección TCP         : {}", self.tcp_address);
+        for listener in &self.listeners {
+            info!("  Dirección adicional   : {}", listener.address);
+        }
+        info!("  Dirección HTTP        : {}", self.http_address.as_deref().unwrap_or("desactivada"));
+        info!("  Dirección WebSocket   : {}", self.websocket_address.as_deref().unwrap_or("desactivada"));
+        match &self.mqtt {
+            Some(m) => info!("  Broker MQTT           : {}:{}", m.broker, m.port),
+            None => info!("  Broker MQTT           : desactivado"),
+        }
+        match &self.modbus {
+            Some(m) => info!("  Modbus TCP            : {} (unit id {:?}, {} decimales)", m.address, m.unit_id, m.decimals),
+            None => info!("  Modbus TCP            : desactivado"),
+        }
+        info!("  Fuera de línea tras   : {} ms", self.offline_after_ms);
         info!("  Recarga configuración : {}", self.recargar_configuracion);
+        info!("  Protocolo             : {}", self.protocol);
+        info!("  Id de báscula         : {}", self.scale_id);
+        info!("  Checksum Toledo       : {}", self.toledo_checksum);
+        match &self.framing {
+            Some(f) => info!("  Framing               : {:?}", f),
+            None => info!("  Framing               : el del protocolo"),
+        }
+        match &self.integrity {
+            Some(i) => info!("  Integridad            : {:?}", i),
+            None => info!("  Integridad            : la del protocolo"),
+        }
+        info!(
+            "  Estabilidad           : {} lecturas ±{} en {} ms",
+            self.stable_readings, self.stable_tolerance, self.stable_timeout_ms
+        );
+        info!("  Intervalo mín. poll   : {} ms", self.min_poll_interval_ms);
+        info!("  Detección (ms)        : {}", self.deteccion_ms);
+        info!("  Comandos TCP          : {}", self.commands.len());
+        let ms = |v: Option<u64>| v.map(|v| format!("{} ms", v)).unwrap_or_else(|| "general".to_string());
+        for (nombre, spec) in &self.commands {
+            info!(
+                "    - {} → {} (serial: {}, espera: {}, caché: {})",
+                nombre,
+                spec.scale.map(|c| format!("{:?}", c)).unwrap_or_else(|| "caché".to_string()),
+                spec.serial.as_deref().map(|s| sanitize_log_data(s.as_bytes())).unwrap_or_else(|| "protocolo".to_string()),
+                ms(spec.wait_ms),
+                ms(spec.cache_ms)
+            );
+        }
+        match &self.filters {
+            Some(reglas) => {
+                info!("  Reglas de filtro      : {}", reglas.len());
+                for regla in reglas {
+                    info!("    - {} ({:?})", regla.nombre(), regla.action);
+                }
+            }
+            None => info!("  Reglas de filtro      : las del protocolo"),
+        }
     }
 
     pub fn address(&self) -> &str {
         &self.tcp_address
     }
+
+    /// Plantillas de respuesta del puerto `address` (las globales si no es un `[[listener]]`).
+    pub fn plantillas(&self, address: &str) -> Plantillas {
+        match self.listeners.iter().find(|l| l.address == address) {
+            Some(listener) => listener.responses.sobre(&self.responses),
+            None => self.responses.clone(),
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq)]
 struct ConfigComparable {
     serial_port: String,
     baud_rate: u32,
@@ -89,10 +240,27 @@ struct ConfigComparable {
     stop_bits: StopBits,
     timeout_ms: u64,
     cache_duration_ms: u64,
+    cache_wait_ms: u64,
     w_duration_ms: u64,
     w_response_timeout_ms: u64,
     tcp_address: String,
+    offline_after_ms: u64,
+    responses: Plantillas,
     recargar_configuracion: bool,
+    scale_id: String,
+    framing: Option<Framing>,
+    filters: Option<Vec<FilterRule>>,
+    integrity: Option<Integrity>,
+    stable_timeout_ms: u64,
+    stable_readings: usize,
+    stable_tolerance: f64,
+    min_poll_interval_ms: u64,
+    deteccion_ms: u64,
+    commands: BTreeMap<String, CommandSpec>,
+    /// Plantillas de los `[[listener]]` (sus direcciones se leen al iniciar)
+    plantillas_listeners: Vec<Plantillas>,
+    /// `unit_id` y `decimals` de `[modbus]` (la dirección se lee al iniciar)
+    modbus: Option<(Option<u8>, u8)>,
 }
 
 impl From<&Config> for ConfigComparable {
@@ -105,17 +273,70 @@ impl From<&Config> for ConfigComparable {
             stop_bits: cfg.stop_bits,
             timeout_ms: cfg.timeout_ms,
             cache_duration_ms: cfg.cache_duration_ms,
+            cache_wait_ms: cfg.cache_wait_ms,
             w_duration_ms: cfg.w_duration_ms,
             w_response_timeout_ms: cfg.w_response_timeout_ms,
             tcp_address: cfg.tcp_address.clone(),
+            offline_after_ms: cfg.offline_after_ms,
+            responses: cfg.responses.clone(),
             recargar_configuracion: cfg.recargar_configuracion,
+            scale_id: cfg.scale_id.clone(),
+            framing: cfg.framing.clone(),
+            filters: cfg.filters.clone(),
+            integrity: cfg.integrity.clone(),
+            stable_timeout_ms: cfg.stable_timeout_ms,
+            stable_readings: cfg.stable_readings,
+            stable_tolerance: cfg.stable_tolerance,
+            min_poll_interval_ms: cfg.min_poll_interval_ms,
+            deteccion_ms: cfg.deteccion_ms,
+            commands: cfg.commands.clone(),
+            plantillas_listeners: cfg.listeners.iter().map(|l| l.responses.clone()).collect(),
+            modbus: cfg.modbus.as_ref().map(|m| (m.unit_id, m.decimals)),
         }
     }
 }
 
+/// Mantiene en `nueva` las claves que solo se leen al iniciar (protocolo,
+/// direcciones de escucha, broker MQTT) y devuelve las que cambiaron.
+fn conservar_claves_de_inicio(actual: &Config, nueva: &mut Config) -> Vec<&'static str> {
+    let mut cambiadas = Vec::new();
+    if nueva.protocol != actual.protocol {
+        nueva.protocol = actual.protocol.clone();
+        cambiadas.push("protocol");
+    }
+    if nueva.toledo_checksum != actual.toledo_checksum {
+        nueva.toledo_checksum = actual.toledo_checksum;
+        cambiadas.push("toledo_checksum");
+    }
+    let direcciones = |c: &Config| c.listeners.iter().map(|l| l.address.clone()).collect::<Vec<_>>();
+    if direcciones(nueva) != direcciones(actual) {
+        nueva.listeners = actual.listeners.clone();
+        cambiadas.push("listener");
+    }
+    if nueva.http_address != actual.http_address {
+        nueva.http_address = actual.http_address.clone();
+        cambiadas.push("http_address");
+    }
+    if nueva.websocket_address != actual.websocket_address {
+        nueva.websocket_address = actual.websocket_address.clone();
+        cambiadas.push("websocket_address");
+    }
+    if nueva.mqtt != actual.mqtt {
+        nueva.mqtt = actual.mqtt.clone();
+        cambiadas.push("mqtt");
+    }
+    if nueva.modbus.as_ref().map(|m| &m.address) != actual.modbus.as_ref().map(|m| &m.address) {
+        nueva.modbus = actual.modbus.clone();
+        cambiadas.push("modbus.address");
+    }
+    cambiadas
+}
+
+#[derive(Clone)]
 pub struct RuntimeConfig {
     pub config: Arc<RwLock<Config>>,
-    pub serial_write_sender: flume::Sender<Vec<u8>>,
+    pub coordinador: Arc<Coordinador>,
+    pub protocol: Arc<dyn ScaleProtocol>,
 }
 
 pub fn init_logging() {
@@ -147,10 +368,16 @@ pub fn spawn_reload_thread(path: &str, shared: Arc<RwLock<Config>>) {
             return;
         }
 
+        let mut avisadas = Vec::new();
         loop {
             thread::sleep(Duration::from_secs(5));
             match Config::load_from_file(&path) {
-                Ok(nueva_config) => {
+                Ok(mut nueva_config) => {
+                    let cambiadas = conservar_claves_de_inicio(&shared.read(), &mut nueva_config);
+                    if !cambiadas.is_empty() && cambiadas != avisadas {
+                        log::warn!("⚠️ Cambios que requieren reinicio (se ignoran hasta entonces): {}", cambiadas.join(", "));
+                    }
+                    avisadas = cambiadas;
                     let nueva_comp = ConfigComparable::from(&nueva_config);
 
                     if !nueva_config.recargar_configuracion {
@@ -0,0 +1,141 @@
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use log::debug;
+use regex::bytes::Regex;
+use serde::Deserialize;
+
+use crate::serial_utils::sanitize_log_data;
+use crate::stats::Stats;
+
+/// Tipo de coincidencia de una regla de filtro.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchKind {
+    Exact,
+    Prefix,
+    Suffix,
+    Contains,
+    Regex,
+}
+
+/// Qué hacer con una trama que coincide con la regla.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    Accept,
+    Reject,
+}
+
+/// Regla de la sección `[[filter]]` del archivo de configuración.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct FilterRule {
+    pub kind: MatchKind,
+    pub pattern: String,
+    #[serde(default = "default_action")]
+    pub action: FilterAction,
+    /// Nombre usado en los contadores; por defecto `kind:pattern`
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+fn default_action() -> FilterAction {
+    FilterAction::Reject
+}
+
+impl FilterRule {
+    fn reject(kind: MatchKind, pattern: &str, name: &str) -> Self {
+        Self {
+            kind,
+            pattern: pattern.to_string(),
+            action: FilterAction::Reject,
+            name: Some(name.to_string()),
+        }
+    }
+
+    pub fn nombre(&self) -> String {
+        self.name.clone().unwrap_or_else(|| {
+            let kind = format!("{:?}", self.kind).to_lowercase();
+            format!("{}:{}", kind, sanitize_log_data(self.pattern.as_bytes()))
+        })
+    }
+}
+
+/// Reglas que antes estaban fijas en el código, ajustadas a la báscula
+/// original del puente. Son las del protocolo `generic` si el archivo no
+/// define `[[filter]]`; los demás protocolos no tienen reglas por defecto.
+pub fn default_rules() -> Vec<FilterRule> {
+    vec![
+        FilterRule::reject(MatchKind::Exact, "\u{18}\r", "can"),
+        FilterRule::reject(MatchKind::Exact, "\u{02}?X\r", "?X"),
+        FilterRule::reject(MatchKind::Exact, "\u{02}?P\r", "?P"),
+        FilterRule::reject(MatchKind::Exact, "\u{02}?D\r", "?D"),
+        FilterRule::reject(MatchKind::Exact, "\u{02}?A\r", "?A"),
+        FilterRule::reject(MatchKind::Exact, "00000", "00000"),
+        FilterRule::reject(MatchKind::Suffix, "0.005\r", "0.005"),
+        FilterRule::reject(MatchKind::Contains, "Count        Weight/kg", "encabezado"),
+    ]
+}
+
+enum Matcher {
+    Bytes(Vec<u8>),
+    Regex(Regex),
+}
+
+/// Reglas de filtro compiladas, con contadores por regla en `Stats`.
+pub struct Filtro {
+    reglas: Vec<FilterRule>,
+    compiladas: Vec<(String, FilterAction, MatchKind, Matcher)>,
+    stats: Arc<Stats>,
+}
+
+impl Filtro {
+    pub fn compilar(reglas: &[FilterRule], stats: Arc<Stats>) -> Result<Self> {
+        let compiladas = reglas
+            .iter()
+            .map(|r| {
+                let matcher = match r.kind {
+                    MatchKind::Regex => Matcher::Regex(
+                        Regex::new(&r.pattern)
+                            .with_context(|| format!("Regex inválida en filtro '{}'", r.nombre()))?,
+                    ),
+                    _ => Matcher::Bytes(r.pattern.as_bytes().to_vec()),
+                };
+                Ok((r.nombre(), r.action, r.kind, matcher))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            reglas: reglas.to_vec(),
+            compiladas,
+            stats,
+        })
+    }
+
+    /// Reglas a partir de las que se compiló (para detectar recargas)
+    pub fn reglas(&self) -> &[FilterRule] {
+        &self.reglas
+    }
+
+    /// La primera regla que coincide decide; si ninguna coincide, la trama se acepta.
+    pub fn es_relevante(&self, data: &[u8]) -> bool {
+        for (nombre, accion, kind, matcher) in &self.compiladas {
+            let coincide = match (kind, matcher) {
+                (_, Matcher::Regex(re)) => re.is_match(data),
+                (MatchKind::Exact, Matcher::Bytes(p)) => data == p.as_slice(),
+                (MatchKind::Prefix, Matcher::Bytes(p)) => data.starts_with(p),
+                (MatchKind::Suffix, Matcher::Bytes(p)) => data.ends_with(p),
+                (_, Matcher::Bytes(p)) => {
+                    p.is_empty() || data.windows(p.len()).any(|w| w == p.as_slice())
+                }
+            };
+
+            if coincide {
+                self.stats.incrementar(&format!("filtro.{}", nombre));
+                debug!("🔎 Regla de filtro '{}' ({:?}): {}", nombre, accion, sanitize_log_data(data));
+                return *accion == FilterAction::Accept;
+            }
+        }
+        true
+    }
+}
@@ -0,0 +1,119 @@
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+
+/// Forma de delimitar las tramas de la báscula, configurable en la sección
+/// `[framing]`. Si no se configura, se usa el framing propio del protocolo.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Framing {
+    /// Tramas terminadas en una secuencia (`"\r"`, `"\n"`, `"\r\n"`, `"\u0003"`...)
+    Terminator { terminator: String },
+    /// Tramas `STX ... ETX`, opcionalmente seguidas de bytes extra (p. ej. BCC)
+    StxEtx {
+        #[serde(default)]
+        trailing_bytes: usize,
+    },
+    /// Tramas de largo fijo, opcionalmente sincronizadas con un prefijo
+    FixedLength {
+        length: usize,
+        #[serde(default)]
+        start: Option<String>,
+    },
+    /// La trama termina cuando la báscula deja de enviar bytes durante `idle_gap_ms`
+    IdleGap { idle_gap_ms: u64 },
+}
+
+impl Framing {
+    /// Extrae la siguiente trama completa de `pending`.
+    /// `ultimo_dato` es el instante en que llegó el último byte (para `IdleGap`).
+    pub fn next_frame(&self, pending: &mut Vec<u8>, ultimo_dato: Option<Instant>) -> Option<Vec<u8>> {
+        match self {
+            Framing::Terminator { terminator } => {
+                let fin = terminator.as_bytes();
+                if fin.is_empty() {
+                    return None;
+                }
+                let pos = pending.windows(fin.len()).position(|w| w == fin)?;
+                Some(pending.drain(..pos + fin.len()).collect())
+            }
+            Framing::StxEtx { trailing_bytes } => {
+                let inicio = match pending.iter().position(|&b| b == STX) {
+                    Some(inicio) => inicio,
+                    None => {
+                        pending.clear();
+                        return None;
+                    }
+                };
+                pending.drain(..inicio);
+                let etx = pending.iter().position(|&b| b == ETX);
+                // Un STX antes del ETX es el inicio de otra trama: descartar la incompleta
+                if let Some(otro) = pending[1..].iter().position(|&b| b == STX).map(|p| p + 1) {
+                    if etx.map(|etx| otro < etx).unwrap_or(true) {
+                        pending.drain(..otro);
+                        return self.next_frame(pending, ultimo_dato);
+                    }
+                }
+                let fin = etx? + 1 + trailing_bytes;
+                if pending.len() < fin {
+                    return None;
+                }
+                Some(pending.drain(..fin).collect())
+            }
+            Framing::FixedLength { length, start } => {
+                if let Some(prefijo) = start.as_ref().map(|s| s.as_bytes()).filter(|p| !p.is_empty()) {
+                    match pending.windows(prefijo.len()).position(|w| w == prefijo) {
+                        Some(inicio) => {
+                            pending.drain(..inicio);
+                        }
+                        None => {
+                            // Conservar solo lo que podría ser el inicio de un prefijo
+                            let conservar = pending.len().min(prefijo.len() - 1);
+                            pending.drain(..pending.len() - conservar);
+                            return None;
+                        }
+                    }
+                }
+                if *length == 0 || pending.len() < *length {
+                    return None;
+                }
+                Some(pending.drain(..*length).collect())
+            }
+            Framing::IdleGap { idle_gap_ms } => {
+                let silencio = ultimo_dato?.elapsed();
+                if pending.is_empty() || silencio < Duration::from_millis(*idle_gap_ms) {
+                    return None;
+                }
+                Some(std::mem::take(pending))
+            }
+        }
+    }
+
+    /// Indica si las tramas pueden completarse sin que lleguen bytes nuevos.
+    pub fn depende_del_tiempo(&self) -> bool {
+        matches!(self, Framing::IdleGap { .. })
+    }
+
+    /// Cada cuánto hay que leer el puerto para medir el silencio con precisión
+    /// (una fracción de `idle_gap_ms`); `None` si el framing no depende del tiempo.
+    pub fn resolucion(&self) -> Option<Duration> {
+        match self {
+            Framing::IdleGap { idle_gap_ms } => Some(Duration::from_millis(idle_gap_ms / 4).max(Duration::from_millis(1))),
+            _ => None,
+        }
+    }
+
+    /// Con `IdleGap`, indica si lo pendiente ya cerró por silencio antes de
+    /// sumarle bytes nuevos (que serían el comienzo de otra trama).
+    pub fn cerrada_por_silencio(&self, pending: &[u8], ultimo_dato: Option<Instant>) -> bool {
+        match (self, ultimo_dato) {
+            (Framing::IdleGap { idle_gap_ms }, Some(ultimo)) => {
+                !pending.is_empty() && ultimo.elapsed() >= Duration::from_millis(*idle_gap_ms)
+            }
+            _ => false,
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::Serialize;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+use crate::cache::SharedCache;
+use crate::command::Comando;
+use crate::config::RuntimeConfig;
+use crate::poll;
+use crate::protocol::ScaleCommand;
+use crate::respuesta::{CodigoError, Respuesta};
+use crate::stats::Stats;
+
+/// Pedidos atendidos a la vez; los demás esperan en la cola del servidor.
+/// `?fresh=1` y los comandos pueden esperar a la báscula varios segundos.
+const HILOS_HTTP: usize = 8;
+/// Rutas `POST` documentadas y el comando de la tabla `[commands]` que ejecutan
+const OPERACIONES: [(&str, &str); 5] = [
+    ("/tare", "TARE"),
+    ("/zero", "ZERO"),
+    ("/print", "PRINT"),
+    ("/clear", "CLEAR"),
+    ("/grossnet", "GROSSNET"),
+];
+
+/// Respuesta de `GET /status`.
+#[derive(Serialize)]
+struct Estado<'a> {
+    scale_id: &'a str,
+    protocol: &'a str,
+    serial_port: &'a str,
+    /// Llegó una trama dentro de `offline_after_ms`
+    online: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_frame_age_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stable: Option<bool>,
+    subscribers: usize,
+    counters: BTreeMap<String, u64>,
+}
+
+/// Inicia la API REST en un hilo propio si `http_address` está configurada.
+pub fn start_http_server(runtime_config: &RuntimeConfig, cache: SharedCache, stats: Arc<Stats>) {
+    let address = match runtime_config.config.read().http_address.clone() {
+        Some(address) => address,
+        None => return,
+    };
+
+    let server = match Server::http(&address) {
+        Ok(server) => server,
+        Err(e) => {
+            warn!("❌ No se pudo iniciar el servidor HTTP en {}: {}", address, e);
+            return;
+        }
+    };
+    info!("🟢 Servidor HTTP escuchando en {}", address);
+
+    let server = Arc::new(server);
+    for _ in 0..HILOS_HTTP {
+        let server = server.clone();
+        let runtime_config = runtime_config.clone();
+        let cache = cache.clone();
+        let stats = stats.clone();
+        thread::spawn(move || loop {
+            match server.recv() {
+                Ok(request) => atender(request, &runtime_config, &cache, &stats),
+                Err(e) => {
+                    warn!("❌ Error recibiendo pedido HTTP: {}", e);
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Responde un pedido HTTP con una línea JSON.
+fn atender(request: Request, runtime_config: &RuntimeConfig, cache: &SharedCache, stats: &Stats) {
+    let url = request.url().to_string();
+    let (ruta, consulta) = url.split_once('?').unwrap_or((url.as_str(), ""));
+    info!("🌐 {} {}", request.method(), url);
+
+    let operacion = OPERACIONES.iter().find(|(r, _)| *r == ruta).map(|(_, nombre)| *nombre);
+    let permitido = match (ruta, operacion) {
+        ("/weight" | "/status", _) => Some("GET"),
+        (_, Some(_)) => Some("POST"),
+        _ => None,
+    };
+
+    let resultado = match (request.method(), ruta, operacion) {
+        (Method::Get, "/weight", _) => peso(runtime_config, cache, consulta),
+        (Method::Get, "/status", _) => Ok(estado(runtime_config, cache, stats)),
+        (Method::Post, _, Some(nombre)) => comando(runtime_config, cache, nombre),
+        _ if permitido.is_some() => {
+            let (_, cuerpo) = json(runtime_config, &Respuesta::Error(CodigoError::InvalidCommand, Some("método no permitido")));
+            Ok((405, cuerpo))
+        }
+        _ => Ok(json(runtime_config, &Respuesta::Error(CodigoError::InvalidCommand, Some("ruta desconocida")))),
+    };
+    let (codigo, cuerpo) = resultado.unwrap_or_else(|e| {
+        warn!("❌ Error atendiendo {}: {:?}", url, e);
+        (500, serde_json::json!({ "ok": false, "error": "INTERNAL", "detail": e.to_string() }).to_string().into_bytes())
+    });
+
+    let mut respuesta = Response::from_data(cuerpo)
+        .with_status_code(codigo)
+        .with_header(Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap());
+    if let (405, Some(metodo)) = (codigo, permitido) {
+        respuesta.add_header(Header::from_bytes(&b"Allow"[..], metodo.as_bytes()).unwrap());
+    }
+    if let Err(e) = request.respond(respuesta) {
+        warn!("⚠️ Error al enviar respuesta HTTP: {}", e);
+    }
+}
+
+/// `GET /weight`: igual que `1`; con `fresh=1`, igual que `W`.
+fn peso(runtime_config: &RuntimeConfig, cache: &SharedCache, consulta: &str) -> Result<(u16, Vec<u8>)> {
+    let fresco = consulta
+        .split('&')
+        .filter_map(|par| par.split_once('='))
+        .any(|(clave, valor)| clave == "fresh" && matches!(valor, "1" | "true"));
+
+    let (comando, intervalo_minimo) = {
+        let config = runtime_config.config.read();
+        let comando = Comando::parse(if fresco { "W" } else { "1" }, &config);
+        (comando, Duration::from_millis(config.min_poll_interval_ms))
+    };
+    let comando = comando.ok_or_else(|| anyhow!("la tabla de comandos no define '1' o 'W'"))?;
+
+    let resultado = poll::ejecutar(
+        &runtime_config.coordinador,
+        runtime_config.protocol.as_ref(),
+        cache,
+        &comando,
+        intervalo_minimo,
+    )?;
+    Ok(json(runtime_config, &Respuesta::from(&resultado)))
+}
+
+/// `POST /tare`, `/zero`...: el comando de operación de la tabla `[commands]` asociado a la ruta.
+fn comando(runtime_config: &RuntimeConfig, cache: &SharedCache, nombre: &str) -> Result<(u16, Vec<u8>)> {
+    let (comando, intervalo_minimo) = {
+        let config = runtime_config.config.read();
+        let comando = Comando::parse(nombre, &config)
+            .filter(|c| !matches!(c.scale, None | Some(ScaleCommand::Poll | ScaleCommand::StablePoll)));
+        (comando, Duration::from_millis(config.min_poll_interval_ms))
+    };
+    let comando = match comando {
+        Some(comando) => comando,
+        None => return Ok(json(runtime_config, &Respuesta::Error(CodigoError::InvalidCommand, Some(nombre)))),
+    };
+
+    let resultado = poll::ejecutar(
+        &runtime_config.coordinador,
+        runtime_config.protocol.as_ref(),
+        cache,
+        &comando,
+        intervalo_minimo,
+    )?;
+    Ok(json(runtime_config, &Respuesta::from(&resultado)))
+}
+
+/// `GET /status`: estado de la báscula y contadores del puente.
+fn estado(runtime_config: &RuntimeConfig, cache: &SharedCache, stats: &Stats) -> (u16, Vec<u8>) {
+    let config = runtime_config.config.read();
+    let guard = cache.lock();
+    let trama = guard.get_trama();
+    let edad = trama.map(|t| t.recibida.elapsed());
+    let lectura = trama.and_then(|t| t.lectura.as_ref());
+
+    let estado = Estado {
+        scale_id: &config.scale_id,
+        protocol: runtime_config.protocol.name(),
+        serial_port: &config.serial_port,
+        online: edad.map(|e| e <= Duration::from_millis(config.offline_after_ms)).unwrap_or(false),
+        last_frame_age_ms: edad.map(|e| e.as_millis() as u64),
+        value: lectura.map(|r| r.value),
+        unit: lectura.map(|r| r.unit.clone()).filter(|u| !u.is_empty()),
+        stable: lectura.map(|r| r.stable),
+        subscribers: guard.suscriptores(),
+        counters: stats.snapshot().into_iter().collect(),
+    };
+    let mut cuerpo = serde_json::to_vec(&estado).unwrap_or_default();
+    cuerpo.push(b'\n');
+    (200, cuerpo)
+}
+
+/// Código HTTP y cuerpo JSON de una respuesta.
+fn json(runtime_config: &RuntimeConfig, respuesta: &Respuesta) -> (u16, Vec<u8>) {
+    let codigo = match respuesta {
+        Respuesta::Dato(_) | Respuesta::Hecho(_) => 200,
+        Respuesta::Error(CodigoError::InvalidCommand, _) => 404,
+        Respuesta::Error(CodigoError::NotStable, _) => 409,
+        Respuesta::Error(CodigoError::NotSupported, _) => 501,
+        Respuesta::Error(CodigoError::ScaleError, _) => 502,
+        Respuesta::Error(CodigoError::NoData, _) => 503,
+        Respuesta::Error(CodigoError::Timeout | CodigoError::NoAck, _) => 504,
+    };
+    let mut cuerpo = respuesta.json(&runtime_config.config.read().scale_id).into_bytes();
+    cuerpo.push(b'\n');
+    (codigo, cuerpo)
+}
@@ -0,0 +1,194 @@
+use serde::Deserialize;
+
+const CR: u8 = 0x0D;
+const LF: u8 = 0x0A;
+const STX: u8 = 0x02;
+const ETX: u8 = 0x03;
+
+/// Algoritmo de checksum de la trama.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChecksumKind {
+    #[default]
+    None,
+    /// XOR de los bytes cubiertos (BCC)
+    Xor,
+    /// Suma de los bytes cubiertos módulo 256
+    Sum,
+    /// Complemento a dos de la suma en 7 bits (Mettler Toledo)
+    TwosComplement7,
+    /// CRC-16/MODBUS, 2 bytes little-endian
+    Crc16,
+}
+
+impl ChecksumKind {
+    fn ancho(&self) -> usize {
+        match self {
+            ChecksumKind::None => 0,
+            ChecksumKind::Crc16 => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// Clase de caracteres admitida en toda la trama salvo los bytes del checksum
+/// (los bytes de framing CR, LF, STX y ETX siempre se admiten).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Charset {
+    #[default]
+    Any,
+    Ascii,
+    Printable,
+    Numeric,
+}
+
+/// Validación de integridad de tramas, sección `[integrity]` del archivo de
+/// configuración. Si no se configura se usa la del protocolo, si tiene.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+pub struct Integrity {
+    #[serde(default)]
+    pub checksum: ChecksumKind,
+    /// Bytes del inicio que no entran en el checksum (p. ej. STX)
+    #[serde(default)]
+    pub skip_start: usize,
+    /// Bytes que siguen al checksum al final de la trama (p. ej. ETX, CR)
+    #[serde(default)]
+    pub skip_end: usize,
+    #[serde(default)]
+    pub min_length: Option<usize>,
+    #[serde(default)]
+    pub max_length: Option<usize>,
+    #[serde(default)]
+    pub charset: Charset,
+}
+
+/// CRC-16/MODBUS (polinomio 0xA001 reflejado, valor inicial 0xFFFF).
+pub fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, &b| {
+        (0..8).fold(crc ^ b as u16, |c, _| {
+            if c & 1 != 0 {
+                (c >> 1) ^ 0xA001
+            } else {
+                c >> 1
+            }
+        })
+    })
+}
+
+impl Integrity {
+    /// Verifica la trama. En caso de falla devuelve el motivo (para contadores y logs).
+    pub fn verificar(&self, frame: &[u8]) -> Result<(), &'static str> {
+        if self.min_length.is_some_and(|min| frame.len() < min) {
+            return Err("largo_minimo");
+        }
+        if self.max_length.is_some_and(|max| frame.len() > max) {
+            return Err("largo_maximo");
+        }
+
+        let ancho = self.checksum.ancho();
+        let fin = frame
+            .len()
+            .checked_sub(self.skip_end + ancho)
+            .filter(|fin| *fin >= self.skip_start)
+            .ok_or("largo_checksum")?;
+        let cubiertos = &frame[self.skip_start..fin];
+        let recibido = &frame[fin..fin + ancho];
+
+        let checksum_ok = match self.checksum {
+            ChecksumKind::None => true,
+            ChecksumKind::Xor => cubiertos.iter().fold(0u8, |acc, &b| acc ^ b) == recibido[0],
+            ChecksumKind::Sum => cubiertos.iter().fold(0u8, |acc, &b| acc.wrapping_add(b)) == recibido[0],
+            ChecksumKind::TwosComplement7 => {
+                cubiertos.iter().fold(recibido[0], |acc, &b| acc.wrapping_add(b)) & 0x7F == 0
+            }
+            ChecksumKind::Crc16 => crc16(cubiertos).to_le_bytes() == [recibido[0], recibido[1]],
+        };
+        if !checksum_ok {
+            return Err("checksum");
+        }
+
+        // El checksum es binario: se excluye, pero los bytes fuera de lo que cubre sí se revisan
+        let mut texto = frame[..fin].iter().chain(&frame[fin + ancho..]);
+        let framing = |b: &u8| matches!(*b, CR | LF | STX | ETX);
+        let charset_ok = match self.charset {
+            Charset::Any => true,
+            Charset::Ascii => texto.all(|b| b.is_ascii()),
+            Charset::Printable => texto.all(|b| b.is_ascii_graphic() || *b == b' ' || framing(b)),
+            Charset::Numeric => texto.all(|b| b.is_ascii_digit() || b" +-.,".contains(b) || framing(b)),
+        };
+        if !charset_ok {
+            return Err("caracteres");
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn con(checksum: ChecksumKind, skip_start: usize, skip_end: usize) -> Integrity {
+        Integrity { checksum, skip_start, skip_end, ..Default::default() }
+    }
+
+    #[test]
+    fn xor() {
+        // STX 123.45 BCC ETX: el BCC cubre solo el peso
+        let integrity = con(ChecksumKind::Xor, 1, 1);
+        assert_eq!(integrity.verificar(b"\x02123.45\x1f\x03"), Ok(()));
+        assert_eq!(integrity.verificar(b"\x02123.46\x1f\x03"), Err("checksum"));
+    }
+
+    #[test]
+    fn suma() {
+        let integrity = con(ChecksumKind::Sum, 0, 1);
+        assert_eq!(integrity.verificar(b"123.45-\r"), Ok(()));
+        assert_eq!(integrity.verificar(b"123.45.\r"), Err("checksum"));
+    }
+
+    #[test]
+    fn complemento_a_dos_7() {
+        // Salida continua Toledo: STX ... CR + checksum
+        let integrity = con(ChecksumKind::TwosComplement7, 0, 0);
+        assert_eq!(integrity.verificar(b"\x0240 001234000000\r#"), Ok(()));
+        assert_eq!(integrity.verificar(b"\x0240 001234000000\r$"), Err("checksum"));
+        // El bit 7 del checksum no cuenta
+        assert_eq!(integrity.verificar(b"\x0240 001234000000\r\xa3"), Ok(()));
+    }
+
+    #[test]
+    fn crc16_modbus() {
+        assert_eq!(crc16(b"123456789"), 0x4B37);
+        let integrity = con(ChecksumKind::Crc16, 0, 0);
+        assert_eq!(integrity.verificar(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xC5, 0xCD]), Ok(()));
+        assert_eq!(integrity.verificar(&[0x01, 0x03, 0x00, 0x00, 0x00, 0x0A, 0xCD, 0xC5]), Err("checksum"));
+        assert_eq!(integrity.verificar(&[0xC5]), Err("largo_checksum"));
+    }
+
+    #[test]
+    fn caracteres() {
+        let con_charset = |charset| Integrity { charset, ..Default::default() };
+        assert_eq!(con_charset(Charset::Printable).verificar(b"\x02 12.345kg\r\n"), Ok(()));
+        assert_eq!(con_charset(Charset::Printable).verificar(b" 12.3\x0045kg\r"), Err("caracteres"));
+        assert_eq!(con_charset(Charset::Numeric).verificar(b"+012.345\r\n"), Ok(()));
+        assert_eq!(con_charset(Charset::Numeric).verificar(b"12.345kg\r"), Err("caracteres"));
+        assert_eq!(con_charset(Charset::Ascii).verificar(b"12\x7f\r"), Ok(()));
+        assert_eq!(con_charset(Charset::Ascii).verificar(b"12\xb0C\r"), Err("caracteres"));
+
+        // El byte de checksum (binario) no se revisa; los que lo siguen sí
+        let integrity = Integrity { charset: Charset::Printable, ..con(ChecksumKind::Xor, 1, 1) };
+        assert_eq!(integrity.verificar(b"\x02123.45\x1f\x03"), Ok(()));
+        let integrity = Integrity { charset: Charset::Printable, ..con(ChecksumKind::Xor, 1, 2) };
+        assert_eq!(integrity.verificar(b"\x02123.45\x1f\x03\x07"), Err("caracteres"));
+    }
+
+    #[test]
+    fn largos() {
+        let integrity = Integrity { min_length: Some(3), max_length: Some(5), ..Default::default() };
+        assert_eq!(integrity.verificar(b"12"), Err("largo_minimo"));
+        assert_eq!(integrity.verificar(b"123456"), Err("largo_maximo"));
+        assert_eq!(integrity.verificar(b"1234"), Ok(()));
+    }
+}
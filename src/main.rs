@@ -6,6 +6,21 @@ mod serial_processor;
 mod serial_utils;
 mod tcp_server;
 mod command;
+mod weight;
+mod protocol;
+mod framing;
+mod filter;
+mod stats;
+mod integrity;
+mod autodetect;
+mod subscription;
+mod poll;
+mod respuesta;
+mod plantilla;
+mod http_server;
+mod websocket_server;
+mod mqtt;
+mod modbus;
 
 use crate::config::{Config, RuntimeConfig};
 use flume::unbounded;
@@ -16,13 +31,16 @@ use anyhow::Result;
 fn main() -> Result<()> {
     config::init_logging();
 
-    // Leer el argumento de línea de comandos (opcional)
-    let args: Vec<String> = std::env::args().collect();
-    let config_path = if args.len() > 1 {
-        args[1].clone()
-    } else {
-        "config.toml".to_string()
-    };
+    // Leer los argumentos de línea de comandos: ruta de configuración (opcional)
+    // y las banderas --detectar / --guardar
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let detectar = args.iter().any(|a| a == "--detectar");
+    let guardar = args.iter().any(|a| a == "--guardar");
+    let config_path = args
+        .iter()
+        .find(|a| !a.starts_with("--"))
+        .cloned()
+        .unwrap_or_else(|| "config.toml".to_string());
 
     log::info!("📄 Cargando configuración desde {}", config_path);
 
@@ -30,12 +48,20 @@ fn main() -> Result<()> {
     let initial_config =
         Config::load_from_file(&config_path).expect("No se pudo cargar el archivo de configuración");
 
+    let protocol = protocol::from_config(&initial_config)?;
+
+    if detectar {
+        return detectar_parametros(&config_path, &initial_config, protocol.as_ref(), guardar);
+    }
+
     let shared_config = Arc::new(parking_lot::RwLock::new(initial_config));
     let cache = cache::SharedCache::default();
+    let stats = Arc::new(stats::Stats::default());
 
     let runtime_config = RuntimeConfig {
         config: shared_config.clone(),
-        serial_write_sender: tx_serial_write.clone(),
+        coordinador: Arc::new(poll::Coordinador::new(tx_serial_write.clone())),
+        protocol: protocol.clone(),
     };
 
     // ⏱️ Lanzar hilo para recargar configuración periódicamente si aplica
@@ -44,9 +70,22 @@ fn main() -> Result<()> {
     // ⚙️ Inicializar puerto serial
     let serial_port = shared_config.read().open_serial_port()?;
     shared_config.read().log_config();
+    log::info!("  Comandos del protocolo: {}", protocol::describe_commands(protocol.as_ref()));
 
     log::info!("✅ Inicializando escucha en puerto serial...");
-    serial_reader::start_serial_reader(serial_port, cache.clone(), rx_serial_write);
+    serial_reader::start_serial_reader(
+        serial_port,
+        cache.clone(),
+        rx_serial_write,
+        protocol,
+        shared_config.clone(),
+        stats.clone(),
+    );
+
+    http_server::start_http_server(&runtime_config, cache.clone(), stats);
+    websocket_server::start_websocket_server(&runtime_config, cache.clone());
+    mqtt::start_mqtt(&runtime_config, cache.clone());
+    modbus::start_modbus_server(&runtime_config, cache.clone());
 
     log::info!("📡 Iniciando servidor TCP...");
     tcp_server::start_tcp_server(&runtime_config, cache);
@@ -54,3 +93,33 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+
+/// Modo `--detectar`: prueba las combinaciones de parámetros seriales,
+/// informa la mejor y, con `--guardar`, la escribe en el archivo de configuración.
+fn detectar_parametros(
+    config_path: &str,
+    config: &Config,
+    protocol: &dyn protocol::ScaleProtocol,
+    guardar: bool,
+) -> Result<()> {
+    let candidatos = autodetect::detectar(config, protocol)?;
+    let mejor = match candidatos.first().filter(|c| c.puntaje() > 0.0) {
+        Some(c) => c,
+        None => anyhow::bail!("No se recibieron datos en {} con ninguna combinación", config.serial_port),
+    };
+
+    log::info!(
+        "🏆 Mejor combinación: {} baudios, {:?}, paridad {:?} (puntaje {:.1})",
+        mejor.baud_rate,
+        mejor.data_bits,
+        mejor.parity,
+        mejor.puntaje()
+    );
+
+    if guardar {
+        autodetect::guardar(config_path, mejor)?;
+        log::info!("💾 Parámetros guardados en {}", config_path);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,443 @@
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::Result;
+use flume::{Receiver, Sender};
+use log::{debug, info, warn};
+use serde::Deserialize;
+
+use crate::cache::SharedCache;
+use crate::command::Comando;
+use crate::config::RuntimeConfig;
+use crate::poll::{self, ResultadoComando, ResultadoPoll};
+use crate::weight::{RangeStatus, WeightMode};
+
+/// Cantidad de registros del mapa (iguales en input y holding registers)
+const REGISTROS: usize = 10;
+/// Coils que disparan comandos: 0 tara, 1 cero, 2 solicitud de peso (`W`)
+const COILS: [&str; 3] = ["TARE", "ZERO", "W"];
+
+const LEER_COILS: u8 = 0x01;
+const LEER_HOLDING: u8 = 0x03;
+const LEER_INPUT: u8 = 0x04;
+const ESCRIBIR_COIL: u8 = 0x05;
+const ESCRIBIR_COILS: u8 = 0x0F;
+
+const FUNCION_ILEGAL: u8 = 0x01;
+const DIRECCION_ILEGAL: u8 = 0x02;
+const VALOR_ILEGAL: u8 = 0x03;
+
+/// Sección `[modbus]`: esclavo Modbus TCP con el último peso interpretado.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ModbusConfig {
+    #[serde(default = "default_address")]
+    pub address: String,
+    /// Unit id aceptado; si falta se responde a cualquiera
+    #[serde(default)]
+    pub unit_id: Option<u8>,
+    /// Decimales del peso entero (registros 0-1): peso × 10^decimals
+    #[serde(default = "default_decimals")]
+    pub decimals: u8,
+}
+
+fn default_address() -> String { "0.0.0.0:502".to_string() }
+fn default_decimals() -> u8 { 3 }
+
+/// Inicia el esclavo Modbus TCP en un hilo propio si hay sección `[modbus]` (se lee al iniciar).
+pub fn start_modbus_server(runtime_config: &RuntimeConfig, cache: SharedCache) {
+    let modbus = match runtime_config.config.read().modbus.clone() {
+        Some(modbus) => modbus,
+        None => return,
+    };
+
+    let listener = match TcpListener::bind(&modbus.address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("❌ No se pudo iniciar el servidor Modbus en {}: {}", modbus.address, e);
+            return;
+        }
+    };
+    info!("🟢 Servidor Modbus TCP escuchando en {}", modbus.address);
+
+    let coils = Coils::iniciar(runtime_config, &cache);
+    let runtime_config = runtime_config.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let runtime_config = runtime_config.clone();
+                    let cache = cache.clone();
+                    let coils = coils.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_client(stream, &runtime_config, &cache, &coils) {
+                            warn!("❌ Error manejando cliente Modbus: {:?}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("⚠️ Error al aceptar conexión Modbus: {}", e),
+            }
+        }
+    });
+}
+
+/// Atiende un PLC: lee pedidos con encabezado MBAP y responde cada uno.
+fn handle_client(mut stream: TcpStream, runtime_config: &RuntimeConfig, cache: &SharedCache, coils: &Coils) -> Result<()> {
+    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+    info!("🔌 PLC conectado por Modbus [{}]", peer);
+    let mut encabezado = [0u8; 7];
+
+    loop {
+        if let Err(e) = stream.read_exact(&mut encabezado) {
+            if e.kind() != ErrorKind::UnexpectedEof {
+                warn!("⚠️ Error al leer del PLC [{}]: {}", peer, e);
+            }
+            info!("🔌 PLC desconectado [{}]", peer);
+            return Ok(());
+        }
+        let protocolo = u16::from_be_bytes([encabezado[2], encabezado[3]]);
+        let largo = u16::from_be_bytes([encabezado[4], encabezado[5]]) as usize;
+        let unidad = encabezado[6];
+        if protocolo != 0 || !(2..=254).contains(&largo) {
+            warn!("⚠️ Encabezado Modbus inválido del PLC [{}], se cierra la conexión", peer);
+            return Ok(());
+        }
+        let mut pdu = vec![0u8; largo - 1];
+        stream.read_exact(&mut pdu)?;
+
+        let modbus = runtime_config.config.read().modbus.clone();
+        if modbus.as_ref().and_then(|m| m.unit_id).map(|id| id != unidad).unwrap_or(false) {
+            continue;
+        }
+        let decimales = modbus.map(|m| m.decimals).unwrap_or_else(default_decimals);
+
+        let respuesta = atender_pdu(&pdu, runtime_config, cache, coils, decimales);
+        let mut trama = Vec::with_capacity(7 + respuesta.len());
+        trama.extend_from_slice(&encabezado[..4]);
+        trama.extend_from_slice(&((respuesta.len() + 1) as u16).to_be_bytes());
+        trama.push(unidad);
+        trama.extend_from_slice(&respuesta);
+        stream.write_all(&trama)?;
+    }
+}
+
+/// Respuesta (PDU) a un pedido Modbus, o la excepción correspondiente.
+fn atender_pdu(pdu: &[u8], runtime_config: &RuntimeConfig, cache: &SharedCache, coils: &Coils, decimales: u8) -> Vec<u8> {
+    let funcion = pdu[0];
+    let palabra = |i: usize| pdu.get(i..i + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize);
+    let excepcion = |codigo: u8| vec![funcion | 0x80, codigo];
+    if ![LEER_COILS, LEER_HOLDING, LEER_INPUT, ESCRIBIR_COIL, ESCRIBIR_COILS].contains(&funcion) {
+        return excepcion(FUNCION_ILEGAL);
+    }
+    let (inicio, cantidad) = match (palabra(1), palabra(3)) {
+        (Some(inicio), Some(cantidad)) => (inicio, cantidad),
+        _ => return excepcion(VALOR_ILEGAL),
+    };
+
+    match funcion {
+        LEER_HOLDING | LEER_INPUT => {
+            if !(1..=125).contains(&cantidad) {
+                return excepcion(VALOR_ILEGAL);
+            }
+            if inicio + cantidad > REGISTROS {
+                return excepcion(DIRECCION_ILEGAL);
+            }
+            let registros = registros(runtime_config, cache, decimales);
+            let mut respuesta = vec![funcion, (cantidad * 2) as u8];
+            for r in &registros[inicio..inicio + cantidad] {
+                respuesta.extend_from_slice(&r.to_be_bytes());
+            }
+            respuesta
+        }
+        LEER_COILS => {
+            if !(1..=2000).contains(&cantidad) {
+                return excepcion(VALOR_ILEGAL);
+            }
+            if inicio + cantidad > COILS.len() {
+                return excepcion(DIRECCION_ILEGAL);
+            }
+            // Las coils son disparadores: siempre se leen apagadas
+            let mut respuesta = vec![funcion, cantidad.div_ceil(8) as u8];
+            respuesta.resize(2 + cantidad.div_ceil(8), 0);
+            respuesta
+        }
+        ESCRIBIR_COIL => {
+            // En esta función el segundo campo es el valor: 0xFF00 enciende, 0x0000 apaga
+            let valor = cantidad;
+            if valor != 0xFF00 && valor != 0x0000 {
+                return excepcion(VALOR_ILEGAL);
+            }
+            if inicio >= COILS.len() {
+                return excepcion(DIRECCION_ILEGAL);
+            }
+            if valor == 0xFF00 {
+                coils.disparar(inicio);
+            }
+            pdu[..5].to_vec()
+        }
+        ESCRIBIR_COILS => {
+            let bytes = pdu.get(6..).unwrap_or_default();
+            if !(1..=1968).contains(&cantidad) || pdu.get(5).map(|n| *n as usize) != Some(cantidad.div_ceil(8)) || bytes.len() < cantidad.div_ceil(8) {
+                return excepcion(VALOR_ILEGAL);
+            }
+            if inicio + cantidad > COILS.len() {
+                return excepcion(DIRECCION_ILEGAL);
+            }
+            for i in 0..cantidad {
+                if bytes[i / 8] & (1 << (i % 8)) != 0 {
+                    coils.disparar(inicio + i);
+                }
+            }
+            pdu[..5].to_vec()
+        }
+        _ => unreachable!(),
+    }
+}
+
+/// Mapa de registros (palabras de 32 bits con la palabra alta primero):
+///
+/// | Registro | Contenido |
+/// |----------|-----------|
+/// | 0-1 | peso entero con signo × 10^`decimals` |
+/// | 2-3 | peso IEEE 754 (f32) |
+/// | 4   | bits de estado: 0 estable, 1 sobrecarga, 2 bajo cero, 3 en línea, 4 neto, 5 lectura válida |
+/// | 5   | `decimals` |
+/// | 6-7 | antigüedad del dato en ms (0xFFFFFFFF sin dato) |
+/// | 8-9 | contador de tramas |
+fn registros(runtime_config: &RuntimeConfig, cache: &SharedCache, decimales: u8) -> [u16; REGISTROS] {
+    let offline_after = Duration::from_millis(runtime_config.config.read().offline_after_ms);
+    let guard = cache.lock();
+    let trama = guard.get_trama();
+    let lectura = trama.and_then(|t| t.lectura.as_ref());
+    let edad = trama.map(|t| t.recibida.elapsed());
+
+    let valor = lectura.map(|r| r.value).unwrap_or(0.0);
+    let entero = (valor * 10f64.powi(decimales as i32)).round().clamp(i32::MIN as f64, i32::MAX as f64) as i32;
+    let mut estado = 0u16;
+    if let Some(r) = lectura {
+        estado |= r.stable as u16;
+        estado |= ((r.range == RangeStatus::Overload) as u16) << 1;
+        estado |= ((r.range == RangeStatus::Underload) as u16) << 2;
+        estado |= ((r.mode == WeightMode::Net) as u16) << 4;
+        estado |= 1 << 5;
+    }
+    if edad.map(|e| e <= offline_after).unwrap_or(false) {
+        estado |= 1 << 3;
+    }
+    let edad_ms = edad.map(|e| e.as_millis().min(u32::MAX as u128) as u32).unwrap_or(u32::MAX);
+    let secuencia = guard.secuencia() as u32;
+
+    let alta = |v: u32| (v >> 16) as u16;
+    let baja = |v: u32| v as u16;
+    let flotante = (valor as f32).to_bits();
+    [
+        alta(entero as u32),
+        baja(entero as u32),
+        alta(flotante),
+        baja(flotante),
+        estado,
+        decimales as u16,
+        alta(edad_ms),
+        baja(edad_ms),
+        alta(secuencia),
+        baja(secuencia),
+    ]
+}
+
+/// Cola de las coils escritas por los PLC: un único hilo ejecuta sus comandos
+/// de a uno, y una coil que ya espera (o se está ejecutando) no se vuelve a
+/// encolar, así un PLC que escribe en bucle no acumula pedidos.
+#[derive(Clone)]
+struct Coils {
+    tx: Sender<usize>,
+    pendientes: Arc<[AtomicBool; COILS.len()]>,
+}
+
+impl Coils {
+    /// Crea la cola sin hilo que la atienda.
+    fn nueva() -> (Self, Receiver<usize>) {
+        // Cada coil está a lo sumo una vez en la cola
+        let (tx, rx) = flume::bounded(COILS.len());
+        (Self { tx, pendientes: Arc::new(Default::default()) }, rx)
+    }
+
+    /// Crea la cola y el hilo que ejecuta sus comandos.
+    fn iniciar(runtime_config: &RuntimeConfig, cache: &SharedCache) -> Self {
+        let (coils, rx) = Self::nueva();
+        let pendientes = coils.pendientes.clone();
+        let runtime_config = runtime_config.clone();
+        let cache = cache.clone();
+        thread::spawn(move || {
+            for coil in rx.iter() {
+                ejecutar(COILS[coil], &runtime_config, &cache);
+                pendientes[coil].store(false, Ordering::Release);
+            }
+        });
+        coils
+    }
+
+    /// Encola el comando de la coil, salvo que ya esté pendiente; responde
+    /// al PLC sin esperar a la báscula.
+    fn disparar(&self, coil: usize) {
+        if self.pendientes[coil].swap(true, Ordering::AcqRel) {
+            debug!("Coil Modbus {} ya pendiente, se ignora", COILS[coil]);
+            return;
+        }
+        if self.tx.try_send(coil).is_err() {
+            self.pendientes[coil].store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Ejecuta el comando de la tabla asociado a una coil.
+fn ejecutar(nombre: &str, runtime_config: &RuntimeConfig, cache: &SharedCache) {
+    let (comando, intervalo_minimo) = {
+        let config = runtime_config.config.read();
+        (Comando::parse(nombre, &config), Duration::from_millis(config.min_poll_interval_ms))
+    };
+    let comando = match comando {
+        Some(comando) => comando,
+        None => {
+            warn!("⚠️ La tabla de comandos no define '{}' para la coil Modbus", nombre);
+            return;
+        }
+    };
+    info!("📥 Coil Modbus: {}", comando.nombre);
+
+    let resultado = poll::ejecutar(
+        &runtime_config.coordinador,
+        runtime_config.protocol.as_ref(),
+        cache,
+        &comando,
+        intervalo_minimo,
+    )
+    .map(|r| match r {
+        ResultadoComando::Peso(ResultadoPoll::Dato(_)) | ResultadoComando::Cache(Some(_)) => "dato recibido".to_string(),
+        otro => format!("{:?}", otro),
+    });
+    match resultado {
+        Ok(r) => info!("📨 Comando {} por Modbus: {}", comando.nombre, r),
+        Err(e) => warn!("❌ Error ejecutando comando Modbus {}: {:?}", comando.nombre, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use flume::Receiver;
+    use parking_lot::RwLock;
+
+    use super::*;
+    use crate::config::Config;
+    use crate::poll::Coordinador;
+    use crate::protocol::GenericProtocol;
+    use crate::weight::parse_frame;
+
+    fn entorno() -> (RuntimeConfig, SharedCache, Receiver<Vec<u8>>) {
+        let config: Config = toml::from_str(
+            r#"
+            serial_port = "/dev/null"
+            baud_rate = 9600
+            data_bits = "8"
+            parity = "None"
+            stop_bits = "1"
+            "#,
+        )
+        .unwrap();
+        let (tx, rx) = flume::unbounded();
+        let runtime_config = RuntimeConfig {
+            config: Arc::new(RwLock::new(config)),
+            coordinador: Arc::new(Coordinador::new(tx)),
+            protocol: Arc::new(GenericProtocol),
+        };
+        (runtime_config, SharedCache::default(), rx)
+    }
+
+    fn leer(pdu: &[u8], runtime_config: &RuntimeConfig, cache: &SharedCache) -> Vec<u16> {
+        let respuesta = atender_pdu(pdu, runtime_config, cache, &Coils::nueva().0, 3);
+        assert_eq!(&respuesta[..2], &[pdu[0], (respuesta.len() - 2) as u8]);
+        respuesta[2..].chunks(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect()
+    }
+
+    #[test]
+    fn registros_sin_dato() {
+        let (runtime_config, cache, _rx) = entorno();
+        let registros = leer(&[LEER_HOLDING, 0, 0, 0, 10], &runtime_config, &cache);
+        assert_eq!(registros, vec![0, 0, 0, 0, 0, 3, 0xFFFF, 0xFFFF, 0, 0]);
+    }
+
+    #[test]
+    fn registros_con_lectura() {
+        let (runtime_config, cache, _rx) = entorno();
+        let data = b"ST,GS,+  12.345kg\r".to_vec();
+        cache.set(data.clone(), parse_frame(&data));
+
+        let registros = leer(&[LEER_INPUT, 0, 0, 0, 10], &runtime_config, &cache);
+        assert_eq!((registros[0] as u32) << 16 | registros[1] as u32, 12345);
+        let flotante = f32::from_bits((registros[2] as u32) << 16 | registros[3] as u32);
+        assert!((flotante - 12.345).abs() < 1e-4);
+        // estable, en línea y lectura válida
+        assert_eq!(registros[4], 0b101001);
+        assert_eq!(registros[9], 1);
+
+        // Un rango parcial devuelve solo esos registros
+        assert_eq!(leer(&[LEER_HOLDING, 0, 4, 0, 2], &runtime_config, &cache), vec![0b101001, 3]);
+    }
+
+    #[test]
+    fn excepciones() {
+        let (runtime_config, cache, _rx) = entorno();
+        let (coils, _cola) = Coils::nueva();
+        let pdu = |pdu: &[u8]| atender_pdu(pdu, &runtime_config, &cache, &coils, 3);
+        assert_eq!(pdu(&[0x2B, 0, 0, 0, 1]), vec![0xAB, FUNCION_ILEGAL]);
+        assert_eq!(pdu(&[LEER_HOLDING, 0, 0]), vec![0x83, VALOR_ILEGAL]);
+        assert_eq!(pdu(&[LEER_INPUT, 0, 8, 0, 3]), vec![0x84, DIRECCION_ILEGAL]);
+        assert_eq!(pdu(&[LEER_INPUT, 0, 0, 0, 0]), vec![0x84, VALOR_ILEGAL]);
+        assert_eq!(pdu(&[LEER_COILS, 0, 2, 0, 2]), vec![0x81, DIRECCION_ILEGAL]);
+        assert_eq!(pdu(&[ESCRIBIR_COIL, 0, 0, 0x12, 0x34]), vec![0x85, VALOR_ILEGAL]);
+        assert_eq!(pdu(&[ESCRIBIR_COIL, 0, 7, 0xFF, 0x00]), vec![0x85, DIRECCION_ILEGAL]);
+        assert_eq!(pdu(&[ESCRIBIR_COILS, 0, 0, 0, 3, 2, 0b111]), vec![0x8F, VALOR_ILEGAL]);
+    }
+
+    #[test]
+    fn coils_siempre_apagadas() {
+        let (runtime_config, cache, _rx) = entorno();
+        let (coils, _cola) = Coils::nueva();
+        assert_eq!(atender_pdu(&[LEER_COILS, 0, 0, 0, 3], &runtime_config, &cache, &coils, 3), vec![LEER_COILS, 1, 0]);
+    }
+
+    #[test]
+    fn escribir_coil_dispara_el_comando() {
+        let (runtime_config, cache, rx) = entorno();
+        let coils = Coils::iniciar(&runtime_config, &cache);
+        let espera = Duration::from_secs(2);
+
+        let pdu = [ESCRIBIR_COIL, 0, 0, 0xFF, 0x00];
+        assert_eq!(atender_pdu(&pdu, &runtime_config, &cache, &coils, 3), pdu.to_vec());
+        assert_eq!(rx.recv_timeout(espera).unwrap(), b"T");
+
+        // Apagar una coil no envía nada
+        atender_pdu(&[ESCRIBIR_COIL, 0, 1, 0x00, 0x00], &runtime_config, &cache, &coils, 3);
+
+        let pdu = [ESCRIBIR_COILS, 0, 1, 0, 2, 1, 0b10];
+        assert_eq!(atender_pdu(&pdu, &runtime_config, &cache, &coils, 3), pdu[..5].to_vec());
+        assert_eq!(rx.recv_timeout(espera).unwrap(), b"W");
+        assert!(rx.is_empty());
+    }
+
+    #[test]
+    fn coil_pendiente_no_se_repite() {
+        let (runtime_config, cache, _rx) = entorno();
+        let (coils, cola) = Coils::nueva();
+        let pulso = [ESCRIBIR_COIL, 0, 0, 0xFF, 0x00];
+        for _ in 0..5 {
+            assert_eq!(atender_pdu(&pulso, &runtime_config, &cache, &coils, 3), pulso.to_vec());
+        }
+        atender_pdu(&[ESCRIBIR_COILS, 0, 0, 0, 3, 1, 0b111], &runtime_config, &cache, &coils, 3);
+        assert_eq!(cola.drain().collect::<Vec<_>>(), vec![0, 1, 2]);
+    }
+}
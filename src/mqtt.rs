@@ -0,0 +1,273 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use flume::RecvTimeoutError;
+use log::{info, warn};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde::Deserialize;
+
+use crate::cache::SharedCache;
+use crate::command::Comando;
+use crate::config::RuntimeConfig;
+use crate::poll;
+use crate::respuesta::{CodigoError, Respuesta};
+
+/// Cada cuánto se revisa si la báscula sigue enviando tramas
+const INTERVALO_ESTADO: Duration = Duration::from_secs(1);
+/// Espera antes de reintentar la conexión con el broker
+const ESPERA_RECONEXION: Duration = Duration::from_secs(5);
+/// Comandos recibidos a la espera de ejecutarse; los que excedan se descartan
+const COLA_COMANDOS: usize = 16;
+
+/// Sección `[mqtt]`: publicación de tramas, lecturas y estado en un broker.
+/// En los tópicos, `{scale_id}` se reemplaza por el id de la báscula.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct MqttConfig {
+    pub broker: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    /// Por defecto `puente_balanzav3-{scale_id}`
+    #[serde(default)]
+    pub client_id: Option<String>,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default = "default_keep_alive_s")]
+    pub keep_alive_s: u64,
+    /// Trama cruda de cada dato aceptado
+    #[serde(default = "default_topic_frame")]
+    pub topic_frame: String,
+    /// Lectura interpretada, en JSON
+    #[serde(default = "default_topic_reading")]
+    pub topic_reading: String,
+    /// Estado del puente y de la báscula (retenido; también es el Last Will)
+    #[serde(default = "default_topic_status")]
+    pub topic_status: String,
+    /// Comandos a ejecutar (`W`, `TARE`, `ZERO`...)
+    #[serde(default = "default_topic_command")]
+    pub topic_command: String,
+    /// Resultado de cada comando, en JSON
+    #[serde(default = "default_topic_result")]
+    pub topic_result: String,
+}
+
+fn default_port() -> u16 { 1883 }
+fn default_keep_alive_s() -> u64 { 30 }
+fn default_topic_frame() -> String { "puente_balanza/{scale_id}/frame".to_string() }
+fn default_topic_reading() -> String { "puente_balanza/{scale_id}/reading".to_string() }
+fn default_topic_status() -> String { "puente_balanza/{scale_id}/status".to_string() }
+fn default_topic_command() -> String { "puente_balanza/{scale_id}/command".to_string() }
+fn default_topic_result() -> String { "puente_balanza/{scale_id}/result".to_string() }
+
+/// Tópicos con el id de la báscula ya reemplazado.
+#[derive(Clone)]
+struct Topicos {
+    frame: String,
+    reading: String,
+    status: String,
+    command: String,
+    result: String,
+}
+
+impl Topicos {
+    fn new(mqtt: &MqttConfig, scale_id: &str) -> Self {
+        let t = |topico: &str| topico.replace("{scale_id}", scale_id);
+        Self {
+            frame: t(&mqtt.topic_frame),
+            reading: t(&mqtt.topic_reading),
+            status: t(&mqtt.topic_status),
+            command: t(&mqtt.topic_command),
+            result: t(&mqtt.topic_result),
+        }
+    }
+}
+
+/// Mensaje de estado: el puente en línea y si la báscula envía tramas.
+fn estado(scale_id: &str, puente: bool, bascula: bool) -> Vec<u8> {
+    serde_json::json!({ "scale_id": scale_id, "online": puente, "scale_online": bascula })
+        .to_string()
+        .into_bytes()
+}
+
+/// Conecta con el broker si hay sección `[mqtt]` (se lee al iniciar) y lanza
+/// los hilos que publican las tramas y atienden el tópico de comandos.
+pub fn start_mqtt(runtime_config: &RuntimeConfig, cache: SharedCache) {
+    let (mqtt, scale_id, offline_after) = {
+        let config = runtime_config.config.read();
+        match config.mqtt.clone() {
+            Some(mqtt) => (mqtt, config.scale_id.clone(), Duration::from_millis(config.offline_after_ms)),
+            None => return,
+        }
+    };
+    let qos = match rumqttc::qos(mqtt.qos) {
+        Ok(qos) => qos,
+        Err(_) => {
+            warn!("❌ QoS MQTT inválido: {} (se admite 0, 1 o 2)", mqtt.qos);
+            return;
+        }
+    };
+    let topicos = Topicos::new(&mqtt, &scale_id);
+
+    let client_id = mqtt.client_id.clone().unwrap_or_else(|| format!("puente_balanzav3-{}", scale_id));
+    let mut opciones = MqttOptions::new(client_id, mqtt.broker.clone(), mqtt.port);
+    opciones.set_keep_alive(Duration::from_secs(mqtt.keep_alive_s.max(5)));
+    opciones.set_last_will(LastWill::new(&topicos.status, estado(&scale_id, false, false), qos, true));
+    if let Some(usuario) = &mqtt.username {
+        opciones.set_credentials(usuario.clone(), mqtt.password.clone().unwrap_or_default());
+    }
+
+    let (client, mut connection) = Client::new(opciones, 64);
+    info!("📡 Conectando al broker MQTT {}:{}...", mqtt.broker, mqtt.port);
+    // Sin conexión no se encolan tramas: la cola del cliente se llenaría y
+    // bloquearía al bucle de eventos al reconectar
+    let conectado = Arc::new(AtomicBool::new(false));
+
+    // Publicación de tramas y del estado de la báscula
+    {
+        let client = client.clone();
+        let topicos = topicos.clone();
+        let scale_id = scale_id.clone();
+        let cache = cache.clone();
+        let conectado = conectado.clone();
+        thread::spawn(move || publicar_tramas(&client, &topicos, qos, &scale_id, &cache, offline_after, &conectado));
+    }
+
+    // Los comandos (`W` puede esperar a la báscula) se ejecutan de a uno en un
+    // hilo propio para no bloquear el bucle de eventos
+    let (comandos, rx_comandos) = flume::bounded::<String>(COLA_COMANDOS);
+    {
+        let client = client.clone();
+        let topico = topicos.result.clone();
+        let runtime_config = runtime_config.clone();
+        let cache = cache.clone();
+        thread::spawn(move || {
+            for texto in rx_comandos.iter() {
+                let resultado = ejecutar(&texto, &runtime_config, &cache);
+                if let Err(e) = client.publish(topico.clone(), qos, false, resultado) {
+                    warn!("⚠️ Error publicando resultado MQTT: {}", e);
+                }
+            }
+        });
+    }
+
+    // Bucle de eventos: se reconecta solo al seguir iterando
+    thread::spawn(move || {
+        for evento in connection.iter() {
+            match evento {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    info!("🟢 Conectado al broker MQTT");
+                    conectado.store(true, Ordering::Relaxed);
+                    let en_linea = cache
+                        .lock()
+                        .get_raw()
+                        .map(|(_, t)| t.elapsed() <= offline_after)
+                        .unwrap_or(false);
+                    // Este hilo es el que vacía la cola: un envío bloqueante aquí no volvería nunca
+                    if let Err(e) = client.try_publish(&topicos.status, qos, true, estado(&scale_id, true, en_linea)) {
+                        warn!("⚠️ No se pudo publicar el estado MQTT: {}", e);
+                    }
+                    if let Err(e) = client.try_subscribe(&topicos.command, qos) {
+                        warn!("⚠️ No se pudo suscribir a {}: {}", topicos.command, e);
+                    }
+                }
+                Ok(Event::Incoming(Packet::Publish(publicacion))) if publicacion.topic == topicos.command => {
+                    let texto = String::from_utf8_lossy(&publicacion.payload).trim().to_string();
+                    // Un comando retenido (p. ej. TARE) se repetiría en cada reconexión
+                    if publicacion.retain {
+                        warn!("⚠️ Comando MQTT retenido ignorado: '{}'", texto);
+                        continue;
+                    }
+                    info!("📥 Comando MQTT: '{}'", texto);
+                    if comandos.try_send(texto).is_err() {
+                        warn!("⚠️ Cola de comandos MQTT llena, se descarta el comando");
+                    }
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    conectado.store(false, Ordering::Relaxed);
+                    warn!("⚠️ Conexión MQTT perdida: {}; reintentando en {:?}", e, ESPERA_RECONEXION);
+                    thread::sleep(ESPERA_RECONEXION);
+                }
+            }
+        }
+    });
+}
+
+/// Publica cada trama nueva (cruda y, si se pudo interpretar, la lectura) y
+/// el cambio de estado de la báscula cuando deja de enviar tramas o vuelve.
+/// Mientras no hay conexión con el broker las tramas se descartan; el estado
+/// se vuelve a publicar al reconectar.
+fn publicar_tramas(
+    client: &Client,
+    topicos: &Topicos,
+    qos: QoS,
+    scale_id: &str,
+    cache: &SharedCache,
+    offline_after: Duration,
+    conectado: &AtomicBool,
+) {
+    let rx = cache.lock().suscribir();
+    let mut ultima: Option<Instant> = None;
+    let mut en_linea = false;
+
+    loop {
+        match rx.recv_timeout(INTERVALO_ESTADO) {
+            Ok(trama) => {
+                ultima = Some(trama.recibida);
+                if conectado.load(Ordering::Relaxed) {
+                    // Con la cola llena (broker lento) se descarta en lugar de bloquear
+                    let _ = client.try_publish(&topicos.frame, qos, false, trama.data.clone());
+                    if trama.lectura.is_some() {
+                        let json = Respuesta::Dato(&trama).json(scale_id);
+                        let _ = client.try_publish(&topicos.reading, qos, false, json);
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+
+        let ahora_en_linea = ultima.map(|t| t.elapsed() <= offline_after).unwrap_or(false);
+        if ahora_en_linea != en_linea {
+            en_linea = ahora_en_linea;
+            info!("📶 Báscula {}", if en_linea { "en línea" } else { "fuera de línea" });
+            if conectado.load(Ordering::Relaxed) {
+                let _ = client.try_publish(&topicos.status, qos, true, estado(scale_id, true, en_linea));
+            }
+        }
+    }
+}
+
+/// Ejecuta un comando recibido por MQTT y devuelve el resultado en JSON.
+fn ejecutar(texto: &str, runtime_config: &RuntimeConfig, cache: &SharedCache) -> Vec<u8> {
+    let (comando, intervalo_minimo, scale_id) = {
+        let config = runtime_config.config.read();
+        let intervalo_minimo = Duration::from_millis(config.min_poll_interval_ms);
+        (Comando::parse(texto, &config), intervalo_minimo, config.scale_id.clone())
+    };
+
+    let resultado = match comando {
+        None => Ok(Respuesta::Error(CodigoError::InvalidCommand, None).json(&scale_id)),
+        Some(comando) => poll::ejecutar(
+            &runtime_config.coordinador,
+            runtime_config.protocol.as_ref(),
+            cache,
+            &comando,
+            intervalo_minimo,
+        )
+        .map(|r| Respuesta::from(&r).json(&scale_id)),
+    };
+
+    resultado
+        .unwrap_or_else(|e| {
+            warn!("❌ Error ejecutando comando MQTT '{}': {:?}", texto, e);
+            serde_json::json!({ "ok": false, "scale_id": scale_id, "error": "INTERNAL", "detail": e.to_string() })
+                .to_string()
+        })
+        .into_bytes()
+}
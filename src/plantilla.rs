@@ -0,0 +1,411 @@
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+
+use crate::cache::Trama;
+use crate::weight::RangeStatus;
+
+/// Placeholder de una plantilla de respuesta.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Campo {
+    /// Peso con los decimales de la báscula
+    Weight,
+    Unit,
+    /// Trama cruda sin los caracteres de control de los extremos
+    Raw,
+    /// Fecha y hora de llegada de la trama
+    Timestamp,
+    /// Antigüedad del dato en ms
+    Age,
+    /// `ST` estable, `US` en movimiento, `OL` sobrecarga, `UL` bajo cero
+    Status,
+    ScaleId,
+    /// Código de error de la báscula
+    Detail,
+}
+
+impl Campo {
+    fn parse(nombre: &str) -> Option<Self> {
+        Some(match nombre {
+            "weight" => Campo::Weight,
+            "unit" => Campo::Unit,
+            "raw" => Campo::Raw,
+            "timestamp" => Campo::Timestamp,
+            "age" => Campo::Age,
+            "status" => Campo::Status,
+            "scale_id" => Campo::ScaleId,
+            "detail" => Campo::Detail,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Alineacion {
+    Izquierda,
+    Derecha,
+    Centro,
+}
+
+/// Especificación de formato de un placeholder, como en `format!`:
+/// `[[relleno]alineación][0][ancho][.precisión]`, p. ej. `{weight:>10.2}` o `{weight:08}`.
+/// En `timestamp` la especificación es un formato de `chrono` (`{timestamp:%H:%M:%S}`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Especificacion {
+    relleno: char,
+    alineacion: Option<Alineacion>,
+    ceros: bool,
+    ancho: usize,
+    precision: Option<usize>,
+    /// Texto original, usado como formato de fecha en `timestamp`
+    fuente: String,
+}
+
+impl Especificacion {
+    fn parse(campo: Campo, spec: &str) -> Result<Self, String> {
+        let mut e = Especificacion {
+            relleno: ' ',
+            alineacion: None,
+            ceros: false,
+            ancho: 0,
+            precision: None,
+            fuente: spec.to_string(),
+        };
+        if e.es_fecha(campo) {
+            // Un formato inválido haría fallar (con pánico) cada respuesta: rechazarlo al cargar
+            if StrftimeItems::new(spec).any(|item| item == Item::Error) {
+                return Err(format!("formato de fecha inválido en '{{timestamp:{}}}'", spec));
+            }
+            return Ok(e);
+        }
+        let alineacion = |c| match c {
+            '<' => Some(Alineacion::Izquierda),
+            '>' => Some(Alineacion::Derecha),
+            '^' => Some(Alineacion::Centro),
+            _ => None,
+        };
+
+        let chars: Vec<char> = spec.chars().collect();
+        let mut i = 0;
+        if chars.len() >= 2 && alineacion(chars[1]).is_some() {
+            e.relleno = chars[0];
+            e.alineacion = alineacion(chars[1]);
+            i = 2;
+        } else if let Some(a) = chars.first().and_then(|c| alineacion(*c)) {
+            e.alineacion = Some(a);
+            i = 1;
+        }
+        if chars.get(i) == Some(&'0') {
+            e.ceros = true;
+            i += 1;
+        }
+        let resto: String = chars[i..].iter().collect();
+        let (ancho, precision) = match resto.split_once('.') {
+            Some((ancho, precision)) => (ancho, Some(precision)),
+            None => (resto.as_str(), None),
+        };
+        if !ancho.is_empty() {
+            e.ancho = ancho.parse().map_err(|_| format!("ancho inválido en '{{:{}}}'", spec))?;
+        }
+        if let Some(p) = precision {
+            e.precision = Some(p.parse().map_err(|_| format!("precisión inválida en '{{:{}}}'", spec))?);
+        }
+        Ok(e)
+    }
+
+    /// La especificación es un formato de fecha de `chrono`.
+    fn es_fecha(&self, campo: Campo) -> bool {
+        campo == Campo::Timestamp && self.fuente.contains('%')
+    }
+
+    /// Aplica relleno y alineación (por defecto a la derecha en números).
+    fn rellenar(&self, texto: String, numero: bool) -> String {
+        let largo = texto.chars().count();
+        if largo >= self.ancho {
+            return texto;
+        }
+        let falta = self.ancho - largo;
+
+        if self.ceros && numero && self.alineacion.is_none() {
+            let (signo, digitos) = match texto.strip_prefix('-') {
+                Some(d) => ("-", d),
+                None => ("", texto.as_str()),
+            };
+            return format!("{}{}{}", signo, "0".repeat(falta), digitos);
+        }
+
+        let relleno = |n: usize| self.relleno.to_string().repeat(n);
+        let alineacion = self
+            .alineacion
+            .unwrap_or(if numero { Alineacion::Derecha } else { Alineacion::Izquierda });
+        match alineacion {
+            Alineacion::Izquierda => format!("{}{}", texto, relleno(falta)),
+            Alineacion::Derecha => format!("{}{}", relleno(falta), texto),
+            Alineacion::Centro => format!("{}{}{}", relleno(falta / 2), texto, relleno(falta - falta / 2)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segmento {
+    Literal(String),
+    Campo(Campo, Especificacion),
+}
+
+/// Plantilla de respuesta con placeholders `{weight}`, `{unit}`, `{raw}`,
+/// `{timestamp}`, `{age}`, `{status}`, `{scale_id}` y `{detail}`.
+/// `{{` y `}}` producen llaves literales.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Plantilla {
+    segmentos: Vec<Segmento>,
+}
+
+impl TryFrom<String> for Plantilla {
+    type Error = String;
+
+    fn try_from(fuente: String) -> Result<Self, Self::Error> {
+        let mut segmentos = Vec::new();
+        let mut literal = String::new();
+        let mut chars = fuente.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(format!("falta '}}' en la plantilla '{}'", fuente)),
+                        }
+                    }
+                    let (nombre, spec) = placeholder.split_once(':').unwrap_or((placeholder.as_str(), ""));
+                    let campo = Campo::parse(nombre.trim())
+                        .ok_or_else(|| format!("placeholder desconocido '{{{}}}' en la plantilla '{}'", nombre, fuente))?;
+                    if !literal.is_empty() {
+                        segmentos.push(Segmento::Literal(std::mem::take(&mut literal)));
+                    }
+                    segmentos.push(Segmento::Campo(campo, Especificacion::parse(campo, spec)?));
+                }
+                '}' => return Err(format!("'}}' sin abrir en la plantilla '{}'", fuente)),
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segmentos.push(Segmento::Literal(literal));
+        }
+        Ok(Plantilla { segmentos })
+    }
+}
+
+/// Datos disponibles para completar una plantilla.
+pub struct Valores<'a> {
+    pub trama: Option<&'a Trama>,
+    pub scale_id: &'a str,
+    pub detail: Option<&'a str>,
+}
+
+impl Plantilla {
+    /// Completa la plantilla; los campos sin valor quedan vacíos.
+    pub fn render(&self, valores: &Valores) -> Vec<u8> {
+        let lectura = valores.trama.and_then(|t| t.lectura.as_ref());
+        let mut salida = String::new();
+
+        for segmento in &self.segmentos {
+            let (campo, spec) = match segmento {
+                Segmento::Literal(texto) => {
+                    salida.push_str(texto);
+                    continue;
+                }
+                Segmento::Campo(campo, spec) => (campo, spec),
+            };
+            let (texto, numero) = match campo {
+                Campo::Weight => match lectura {
+                    Some(r) => {
+                        let decimales = spec.precision.unwrap_or(r.decimals as usize);
+                        let signo = if r.negative { "-" } else { "" };
+                        (format!("{}{:.*}", signo, decimales, r.value.abs()), true)
+                    }
+                    None => (String::new(), false),
+                },
+                Campo::Unit => (lectura.map(|r| r.unit.clone()).unwrap_or_default(), false),
+                Campo::Raw => (
+                    valores
+                        .trama
+                        .map(|t| String::from_utf8_lossy(&t.data).trim_matches(|c: char| c.is_control()).to_string())
+                        .unwrap_or_default(),
+                    false,
+                ),
+                Campo::Timestamp => (
+                    valores
+                        .trama
+                        .map(|t| match spec.es_fecha(*campo) {
+                            true => t.fecha.format(&spec.fuente).to_string(),
+                            false => t.fecha.to_rfc3339_opts(chrono::SecondsFormat::Millis, false),
+                        })
+                        .unwrap_or_default(),
+                    false,
+                ),
+                Campo::Age => match valores.trama {
+                    Some(t) => (t.recibida.elapsed().as_millis().to_string(), true),
+                    None => (String::new(), false),
+                },
+                Campo::Status => (
+                    lectura
+                        .map(|r| match (r.range, r.stable) {
+                            (RangeStatus::Overload, _) => "OL",
+                            (RangeStatus::Underload, _) => "UL",
+                            (RangeStatus::Normal, true) => "ST",
+                            (RangeStatus::Normal, false) => "US",
+                        })
+                        .unwrap_or_default()
+                        .to_string(),
+                    false,
+                ),
+                Campo::ScaleId => (valores.scale_id.to_string(), false),
+                Campo::Detail => (valores.detail.unwrap_or_default().to_string(), false),
+            };
+            if spec.es_fecha(*campo) {
+                salida.push_str(&texto);
+            } else {
+                salida.push_str(&spec.rellenar(texto, numero));
+            }
+        }
+        salida.into_bytes()
+    }
+}
+
+/// Sección `[responses]`: plantillas que reemplazan las respuestas de texto.
+/// Las que se omiten conservan la respuesta histórica.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(default)]
+pub struct Plantillas {
+    /// Respuesta con una trama; por defecto la trama cruda
+    pub data: Option<Plantilla>,
+    pub no_data: Option<Plantilla>,
+    pub timeout: Option<Plantilla>,
+    pub not_stable: Option<Plantilla>,
+    pub invalid_command: Option<Plantilla>,
+    /// Error informado por la báscula (`{detail}` es su código)
+    pub scale_error: Option<Plantilla>,
+    pub no_ack: Option<Plantilla>,
+    pub not_supported: Option<Plantilla>,
+}
+
+impl Plantillas {
+    /// Estas plantillas completadas con las de `base` donde falten.
+    pub fn sobre(&self, base: &Plantillas) -> Plantillas {
+        let elegir = |propia: &Option<Plantilla>, base: &Option<Plantilla>| propia.clone().or_else(|| base.clone());
+        Plantillas {
+            data: elegir(&self.data, &base.data),
+            no_data: elegir(&self.no_data, &base.no_data),
+            timeout: elegir(&self.timeout, &base.timeout),
+            not_stable: elegir(&self.not_stable, &base.not_stable),
+            invalid_command: elegir(&self.invalid_command, &base.invalid_command),
+            scale_error: elegir(&self.scale_error, &base.scale_error),
+            no_ack: elegir(&self.no_ack, &base.no_ack),
+            not_supported: elegir(&self.not_supported, &base.not_supported),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use chrono::{Local, TimeZone};
+
+    use super::*;
+    use crate::weight::{parse_frame, WeightReading};
+
+    fn trama(texto: &str) -> Trama {
+        Trama {
+            data: texto.as_bytes().to_vec(),
+            lectura: parse_frame(texto.as_bytes()),
+            recibida: Instant::now(),
+            fecha: Local.with_ymd_and_hms(2026, 3, 4, 5, 6, 7).unwrap(),
+        }
+    }
+
+    fn render(fuente: &str, trama: Option<&Trama>) -> String {
+        let plantilla = Plantilla::try_from(fuente.to_string()).unwrap();
+        let valores = Valores { trama, scale_id: "b1", detail: Some("ES") };
+        String::from_utf8(plantilla.render(&valores)).unwrap()
+    }
+
+    #[test]
+    fn ancho_fijo_y_alineacion() {
+        let t = trama("ST,GS,+  12.345kg\r");
+        assert_eq!(render("{weight:>10}", Some(&t)), "    12.345");
+        assert_eq!(render("{weight:<10}|", Some(&t)), "12.345    |");
+        assert_eq!(render("{weight:*^10}", Some(&t)), "**12.345**");
+        assert_eq!(render("{unit:>4}", Some(&t)), "  kg");
+    }
+
+    #[test]
+    fn relleno_con_ceros_y_signo() {
+        let t = trama("ST,GS,-   1.500kg\r");
+        assert_eq!(render("{weight:08}", Some(&t)), "-001.500");
+        assert_eq!(render("{weight:09.1}", Some(&t)), "-000001.5");
+    }
+
+    #[test]
+    fn peso_con_unidad_y_crlf() {
+        let t = trama("ST,GS,+  12.345kg\r");
+        assert_eq!(render("{weight} {unit}\r\n", Some(&t)), "12.345 kg\r\n");
+        assert_eq!(render("{status} {weight:.1}{unit}", Some(&t)), "ST 12.3kg");
+        assert_eq!(render("{raw}", Some(&t)), "ST,GS,+  12.345kg");
+    }
+
+    #[test]
+    fn fecha_id_y_detalle() {
+        let t = trama("ST,GS,+  12.345kg\r");
+        assert_eq!(render("{timestamp:%H:%M:%S}", Some(&t)), "05:06:07");
+        assert_eq!(render("{scale_id}:{detail}", Some(&t)), "b1:ES");
+    }
+
+    #[test]
+    fn llaves_literales_y_campos_sin_dato() {
+        assert_eq!(render("{{weight}} [{weight}{unit}]", None), "{weight} []");
+        let sin_lectura = Trama { lectura: None, ..trama("???\r") };
+        assert_eq!(render("[{weight:>5}]", Some(&sin_lectura)), "[     ]");
+    }
+
+    #[test]
+    fn estado_de_rango() {
+        let lectura = WeightReading { range: RangeStatus::Overload, ..parse_frame(b"ST 1.0kg").unwrap() };
+        let t = Trama { lectura: Some(lectura), ..trama("OL\r") };
+        assert_eq!(render("{status}", Some(&t)), "OL");
+    }
+
+    #[test]
+    fn errores_de_sintaxis() {
+        let error = |fuente: &str| Plantilla::try_from(fuente.to_string()).unwrap_err();
+        assert!(error("{weight").contains("falta '}'"));
+        assert!(error("{peso}").contains("placeholder desconocido '{peso}'"));
+        assert!(error("weight}").contains("'}' sin abrir"));
+        assert!(error("{weight:x}").contains("ancho inválido"));
+        assert!(error("{weight:5.x}").contains("precisión inválida"));
+        assert!(error("{timestamp:%Q}").contains("formato de fecha inválido"));
+        assert!(error("{timestamp:%H:%}").contains("formato de fecha inválido"));
+    }
+
+    #[test]
+    fn plantillas_por_puerto_sobre_las_globales() {
+        let plantilla = |fuente: &str| Plantilla::try_from(fuente.to_string()).ok();
+        let global = Plantillas { data: plantilla("G"), no_data: plantilla("N"), ..Default::default() };
+        let propias = Plantillas { data: plantilla("P"), ..Default::default() };
+        let combinadas = propias.sobre(&global);
+        assert_eq!(combinadas.data, propias.data);
+        assert_eq!(combinadas.no_data, global.no_data);
+        assert_eq!(combinadas.timeout, None);
+    }
+}
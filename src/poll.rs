@@ -0,0 +1,346 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use flume::Sender;
+use log::{debug, info, warn};
+use parking_lot::Mutex;
+
+use crate::cache::{Cache, SharedCache, Trama};
+use crate::command::{Comando, Resultado};
+use crate::protocol::{ScaleCommand, ScaleProtocol, ScaleResponse};
+
+/// Resultado de pedir un peso a la báscula.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultadoPoll {
+    /// Trama vigente (de la caché o recién llegada)
+    Dato(Trama),
+    /// La báscula rechazó la solicitud, con su código
+    Error(String),
+    /// No llegó una trama nueva dentro del tiempo de espera
+    Timeout,
+    /// El peso no se estabilizó dentro del tiempo de espera
+    NoEstable,
+}
+
+/// Resultado de un comando de la tabla `[commands]`, según su tipo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResultadoComando {
+    /// Lectura de la caché (`1`); `None` si no hay dato vigente
+    Cache(Option<Trama>),
+    /// Pedido de peso (`W`, `S`)
+    Peso(ResultadoPoll),
+    /// Comando de operación (tara, cero...)
+    Operacion(Resultado),
+}
+
+/// Criterio de estabilidad para `S`/`STABLE` cuando la trama no informa movimiento.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CriterioEstable {
+    pub lecturas: usize,
+    pub tolerancia: f64,
+}
+
+/// Solicitud de peso enviada a la báscula y aún sin respuesta.
+struct EnCurso {
+    bytes: Vec<u8>,
+    envio: Instant,
+    limite: Instant,
+}
+
+/// Coordina las solicitudes de peso de todos los clientes: mientras hay una
+/// en curso, los pedidos iguales se suman a ella en lugar de reenviarse, y
+/// entre dos envíos se respeta `min_poll_interval_ms`.
+pub struct Coordinador {
+    sender: Sender<Vec<u8>>,
+    estado: Mutex<Option<EnCurso>>,
+}
+
+impl Coordinador {
+    pub fn new(sender: Sender<Vec<u8>>) -> Self {
+        Self { sender, estado: Mutex::new(None) }
+    }
+
+    /// Envía `bytes` a la báscula, o se suma a una solicitud idéntica en curso.
+    /// Devuelve el instante desde el cual una trama cuenta como respuesta.
+    pub fn solicitar(
+        &self,
+        cache: &SharedCache,
+        bytes: Vec<u8>,
+        espera: Duration,
+        intervalo_minimo: Duration,
+    ) -> Result<Instant> {
+        let mut estado = self.estado.lock();
+
+        if let Some(en_curso) = estado.as_ref() {
+            let respondida = cache
+                .lock()
+                .get_raw()
+                .map(|(_, t)| t >= en_curso.envio)
+                .unwrap_or(false);
+            if !respondida && en_curso.bytes == bytes && Instant::now() < en_curso.limite {
+                debug!("🔗 Solicitud de peso en curso, se espera la misma respuesta");
+                return Ok(en_curso.envio);
+            }
+
+            // Los demás clientes quedan bloqueados en el mutex y luego se suman a este envío
+            let proximo = en_curso.envio + intervalo_minimo;
+            let ahora = Instant::now();
+            if ahora < proximo {
+                thread::sleep(proximo - ahora);
+            }
+        }
+
+        let envio = Instant::now();
+        info!("📤 Enviando solicitud de peso a la báscula...");
+        self.sender.send(bytes.clone()).context("Error enviando 'W' al serial")?;
+        *estado = Some(EnCurso { bytes, envio, limite: envio + espera });
+        Ok(envio)
+    }
+
+    /// Envía bytes que no son solicitudes de peso (tara, cero...).
+    pub fn enviar(&self, bytes: Vec<u8>) -> Result<()> {
+        self.sender.send(bytes).context("Error enviando comando al serial")
+    }
+}
+
+/// Ejecuta un comando de la tabla `[commands]`: única puerta de entrada de
+/// todas las interfaces (TCP, HTTP, WebSocket, MQTT, Modbus).
+pub fn ejecutar(
+    coordinador: &Coordinador,
+    protocol: &dyn ScaleProtocol,
+    cache: &SharedCache,
+    comando: &Comando,
+    intervalo_minimo: Duration,
+) -> Result<ResultadoComando> {
+    Ok(match comando.scale {
+        None => {
+            let trama = leer_cache(cache, comando.frescura, comando.espera);
+            if trama.is_none() {
+                warn!("⚠️ No se encontró dato válido en caché según el criterio.");
+            }
+            ResultadoComando::Cache(trama)
+        }
+        Some(ScaleCommand::Poll | ScaleCommand::StablePoll) => {
+            ResultadoComando::Peso(leer_peso(coordinador, protocol, cache, comando, intervalo_minimo)?)
+        }
+        Some(_) => {
+            let resultado = enviar_comando(coordinador, protocol, cache, comando)?;
+            info!("📨 Comando {}: {:?}", comando.nombre, resultado);
+            ResultadoComando::Operacion(resultado)
+        }
+    })
+}
+
+/// Lógica de `1` compartida por todas las interfaces: devuelve el dato de la
+/// caché si tiene a lo sumo `frescura`; si no, espera hasta `espera` la próxima trama.
+pub fn leer_cache(cache: &SharedCache, frescura: Duration, espera: Duration) -> Option<Trama> {
+    let inicio = Instant::now();
+    let limite = inicio + espera;
+    let mut guard = cache.lock();
+    loop {
+        if let Some(trama) = guard.get_trama() {
+            if trama.recibida.elapsed() <= frescura || trama.recibida >= inicio {
+                return Some(trama.clone());
+            }
+        }
+        if Instant::now() >= limite {
+            return None;
+        }
+        let version = guard.version();
+        drop(guard);
+        guard = cache.esperar_cambio(version, limite);
+    }
+}
+
+/// Lógica de `W` (o `S`/`STABLE`, peso estable) compartida por todas las
+/// interfaces: usa la caché si es reciente; si no, pide un dato nuevo (una sola
+/// vez para todos los clientes simultáneos) y espera la próxima trama o un error.
+pub fn leer_peso(
+    coordinador: &Coordinador,
+    protocol: &dyn ScaleProtocol,
+    cache: &SharedCache,
+    comando: &Comando,
+    intervalo_minimo: Duration,
+) -> Result<ResultadoPoll> {
+    let solo_estable = comando.scale == Some(ScaleCommand::StablePoll);
+
+    // Paso 1: Intentar usar caché reciente (con `S`, solo si informa estar estable)
+    {
+        let guard = cache.lock();
+        if let Some(trama) = guard.get_trama() {
+            let estable = protocol.reports_motion(&trama.data)
+                && trama.lectura.as_ref().map(|r| r.stable).unwrap_or(false);
+            if trama.recibida.elapsed() <= comando.frescura && (estable || !solo_estable) {
+                return Ok(ResultadoPoll::Dato(trama.clone()));
+            }
+        }
+    }
+
+    if solo_estable {
+        return leer_estable(coordinador, protocol, cache, comando, intervalo_minimo);
+    }
+
+    // Tomar la versión antes de enviar para no perder una respuesta inmediata
+    let mut version = cache.lock().version();
+
+    // Paso 2: Solicitar dato nuevo (si el protocolo lo permite; si no, esperar la próxima trama)
+    let bytes = comando
+        .bytes(protocol)
+        .or_else(|| protocol.command(ScaleCommand::Poll));
+    let inicio = match bytes {
+        Some(bytes) => coordinador.solicitar(cache, bytes, comando.espera, intervalo_minimo)?,
+        None => {
+            info!("⏳ Cache inválida/vencida. El protocolo '{}' no admite solicitud, esperando próxima trama...", protocol.name());
+            Instant::now()
+        }
+    };
+    let limite = Instant::now() + comando.espera;
+
+    // Paso 3: Esperar la próxima trama o respuesta de la báscula
+    loop {
+        let guard = cache.esperar_cambio(version, limite);
+        version = guard.version();
+
+        if let Some(trama) = guard.get_trama().filter(|t| t.recibida >= inicio) {
+            return Ok(ResultadoPoll::Dato(trama.clone()));
+        }
+        // La báscula rechazó la solicitud: informar el código al cliente
+        if let Some(codigo) = error_de_bascula(&guard, protocol, ScaleCommand::Poll, inicio) {
+            return Ok(ResultadoPoll::Error(codigo));
+        }
+        if Instant::now() >= limite {
+            warn!("⏱️ Timeout esperando nuevo dato luego de 'W'");
+            return Ok(ResultadoPoll::Timeout);
+        }
+    }
+}
+
+/// Error informado por la báscula para `cmd` después de `inicio`, si lo hay.
+fn error_de_bascula(guard: &Cache, protocol: &dyn ScaleProtocol, cmd: ScaleCommand, inicio: Instant) -> Option<String> {
+    match guard.get_response() {
+        Some((respuesta @ ScaleResponse::Error(codigo), t)) if t >= inicio && protocol.responds_to(cmd, respuesta) => {
+            warn!("⚠️ La báscula respondió con error: {}", codigo);
+            Some(codigo.clone())
+        }
+        _ => None,
+    }
+}
+
+/// Espera hasta `comando.espera` un peso estable, pidiendo cada lectura a las
+/// básculas por demanda (con el comando propio de peso estable si el protocolo
+/// lo tiene, como `S` en MT-SICS). Si la trama informa movimiento se usa ese
+/// indicador; si no, se exigen `criterio.lecturas` lecturas consecutivas
+/// dentro de `criterio.tolerancia`.
+fn leer_estable(
+    coordinador: &Coordinador,
+    protocol: &dyn ScaleProtocol,
+    cache: &SharedCache,
+    comando: &Comando,
+    intervalo_minimo: Duration,
+) -> Result<ResultadoPoll> {
+    let limite = Instant::now() + comando.espera;
+    let criterio = comando.criterio;
+    let (enviado, solicitud) = match comando.bytes(protocol) {
+        Some(bytes) => (ScaleCommand::StablePoll, Some(bytes)),
+        None => (ScaleCommand::Poll, protocol.command(ScaleCommand::Poll)),
+    };
+    let mut ventana: Vec<(f64, String)> = Vec::new();
+    let mut version = cache.lock().version();
+
+    loop {
+        // Básculas por demanda: pedir cada lectura (respetando el intervalo mínimo)
+        let inicio = match &solicitud {
+            Some(bytes) => coordinador.solicitar(cache, bytes.clone(), limite.saturating_duration_since(Instant::now()), intervalo_minimo)?,
+            None => Instant::now(),
+        };
+
+        let trama = loop {
+            let guard = cache.esperar_cambio(version, limite);
+            version = guard.version();
+            if let Some(trama) = guard.get_trama().filter(|t| t.recibida >= inicio) {
+                break trama.clone();
+            }
+            if let Some(codigo) = error_de_bascula(&guard, protocol, enviado, inicio) {
+                return Ok(ResultadoPoll::Error(codigo));
+            }
+            if Instant::now() >= limite {
+                warn!("⏱️ El peso no se estabilizó a tiempo");
+                return Ok(ResultadoPoll::NoEstable);
+            }
+        };
+
+        let lectura = match trama.lectura.clone() {
+            Some(l) => l,
+            None => {
+                ventana.clear();
+                continue;
+            }
+        };
+
+        if protocol.reports_motion(&trama.data) {
+            if lectura.stable {
+                return Ok(ResultadoPoll::Dato(trama));
+            }
+            ventana.clear();
+            continue;
+        }
+
+        if ventana.last().map(|(_, u)| *u != lectura.unit).unwrap_or(false) {
+            ventana.clear();
+        }
+        ventana.push((lectura.value, lectura.unit.clone()));
+        if ventana.len() > criterio.lecturas {
+            ventana.remove(0);
+        }
+        let (min, max) = ventana
+            .iter()
+            .fold((f64::MAX, f64::MIN), |(min, max), (v, _)| (min.min(*v), max.max(*v)));
+        if ventana.len() >= criterio.lecturas.max(1) && max - min <= criterio.tolerancia {
+            return Ok(ResultadoPoll::Dato(trama));
+        }
+    }
+}
+
+/// Envía a la báscula un comando de operación (tara, cero, impresión...) y,
+/// si el protocolo lo confirma, espera su respuesta durante el tiempo de
+/// espera del comando.
+pub fn enviar_comando(
+    coordinador: &Coordinador,
+    protocol: &dyn ScaleProtocol,
+    cache: &SharedCache,
+    comando: &Comando,
+) -> Result<Resultado> {
+    let (cmd, bytes) = match (comando.scale, comando.bytes(protocol)) {
+        (Some(cmd), Some(bytes)) => (cmd, bytes),
+        _ => return Ok(Resultado::NoSoportado),
+    };
+
+    let mut version = cache.lock().version();
+    let inicio = Instant::now();
+    let limite = inicio + comando.espera;
+    info!("📤 Enviando comando {} a la báscula...", comando.nombre);
+    coordinador.enviar(bytes)?;
+
+    // Sin confirmación del protocolo no hay nada que esperar
+    if !protocol.acknowledges(cmd) {
+        return Ok(Resultado::Enviado);
+    }
+
+    while Instant::now() < limite {
+        let guard = cache.esperar_cambio(version, limite);
+        version = guard.version();
+        if let Some((respuesta, t)) = guard.get_response() {
+            // Solo cuenta una respuesta a este tipo de comando (no la tara de otro cliente)
+            if t >= inicio && protocol.responds_to(cmd, respuesta) {
+                return Ok(match respuesta {
+                    ScaleResponse::Ack(_) => Resultado::Ok,
+                    ScaleResponse::Error(codigo) => Resultado::Error(codigo.clone()),
+                });
+            }
+        }
+    }
+
+    warn!("⏱️ La báscula no confirmó el comando {}", comando.nombre);
+    Ok(Resultado::SinConfirmacion)
+}
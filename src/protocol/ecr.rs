@@ -0,0 +1,336 @@
+use crate::integrity::{ChecksumKind, Integrity};
+use crate::weight::{RangeStatus, WeightMode, WeightReading};
+
+use super::{ScaleCommand, ScaleProtocol, ScaleResponse};
+
+const SOH: u8 = 0x01;
+const ETX: u8 = 0x03;
+const EOT: u8 = 0x04;
+const ENQ: u8 = 0x05;
+const ACK: u8 = 0x06;
+const DC1: u8 = 0x11;
+const NAK: u8 = 0x15;
+const LF: u8 = 0x0A;
+const CR: u8 = 0x0D;
+
+/// Separa el peso (`12.345`) de la unidad (`kg`) en un campo como `12.345kg`.
+fn peso_y_unidad(campo: &str) -> Option<(f64, u8, bool, String)> {
+    let campo = campo.trim();
+    let corte = campo
+        .find(|c: char| c.is_ascii_alphabetic())
+        .unwrap_or(campo.len());
+    let numero = campo[..corte].replace(' ', "");
+    let unit = campo[corte..].trim().to_lowercase();
+    let value = numero.parse::<f64>().ok()?;
+    let decimals = numero.split('.').nth(1).map(|d| d.len() as u8).unwrap_or(0);
+    Some((value, decimals, numero.starts_with('-'), unit))
+}
+
+/// Protocolo ECR NCI / Weigh-Tronix 3835: el host envía `W<CR>` y recibe
+/// `<LF>peso<CR><LF>estado<CR><ETX>`, o solo `<LF>estado<CR><ETX>` si la
+/// báscula está en movimiento o con error.
+pub struct NciProtocol;
+
+/// Bits de los bytes de estado NCI.
+struct EstadoNci {
+    movimiento: bool,
+    en_cero: bool,
+    bajo_capacidad: bool,
+    sobre_capacidad: bool,
+}
+
+impl EstadoNci {
+    fn decodificar(linea: &[u8]) -> Option<Self> {
+        // La respuesta al comando S antepone la letra 'S' a los bytes de estado
+        let bytes = linea.strip_prefix(b"S").unwrap_or(linea);
+        let (b1, b2) = (*bytes.first()?, *bytes.get(1)?);
+        // Bits 4 y 5 siempre en 1: descarta líneas que no son de estado
+        if b1 & 0x30 != 0x30 || b2 & 0x30 != 0x30 {
+            return None;
+        }
+        Some(Self {
+            movimiento: b1 & 0x01 != 0,
+            en_cero: b1 & 0x02 != 0,
+            bajo_capacidad: b2 & 0x01 != 0,
+            sobre_capacidad: b2 & 0x02 != 0,
+        })
+    }
+
+    fn rango(&self) -> RangeStatus {
+        if self.sobre_capacidad {
+            RangeStatus::Overload
+        } else if self.bajo_capacidad {
+            RangeStatus::Underload
+        } else {
+            RangeStatus::Normal
+        }
+    }
+}
+
+fn lineas(frame: &[u8]) -> Vec<&[u8]> {
+    frame
+        .split(|&b| b == LF || b == CR || b == ETX)
+        .filter(|l| !l.is_empty())
+        .collect()
+}
+
+impl ScaleProtocol for NciProtocol {
+    fn name(&self) -> &'static str {
+        "nci"
+    }
+
+    fn next_frame(&self, pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+        let pos = pending.iter().position(|&b| b == ETX)?;
+        Some(pending.drain(..=pos).collect())
+    }
+
+    fn is_relevant(&self, frame: &[u8]) -> bool {
+        !lineas(frame).is_empty()
+    }
+
+    fn parse(&self, frame: &[u8]) -> Option<WeightReading> {
+        let lineas = lineas(frame);
+        let estado = EstadoNci::decodificar(lineas.last()?)?;
+
+        match lineas.as_slice() {
+            [peso, _] => {
+                let (value, decimals, negative, unit) =
+                    peso_y_unidad(&String::from_utf8_lossy(peso))?;
+                Some(WeightReading {
+                    value,
+                    decimals,
+                    unit,
+                    negative,
+                    mode: WeightMode::Gross,
+                    stable: !estado.movimiento,
+                    range: estado.rango(),
+                    tare: None,
+                    at_zero: estado.en_cero,
+                })
+            }
+            // Solo estado: únicamente es una lectura si indica fuera de rango
+            [_] if estado.rango() != RangeStatus::Normal => Some(WeightReading {
+                value: 0.0,
+                decimals: 0,
+                unit: String::new(),
+                negative: false,
+                mode: WeightMode::Gross,
+                stable: !estado.movimiento,
+                range: estado.rango(),
+                tare: None,
+                at_zero: estado.en_cero,
+            }),
+            _ => None,
+        }
+    }
+
+    fn response(&self, frame: &[u8]) -> Option<ScaleResponse> {
+        // Comando no reconocido: la báscula responde "?"
+        match lineas(frame).as_slice() {
+            [linea] if linea.starts_with(b"?") => Some(ScaleResponse::Error("?".to_string())),
+            _ => None,
+        }
+    }
+
+    fn reports_motion(&self, _frame: &[u8]) -> bool {
+        true
+    }
+
+    fn command(&self, cmd: ScaleCommand) -> Option<Vec<u8>> {
+        match cmd {
+            ScaleCommand::Poll => Some(b"W\r".to_vec()),
+            ScaleCommand::Zero => Some(b"Z\r".to_vec()),
+            ScaleCommand::Diagnostics => Some(b"S\r".to_vec()),
+            ScaleCommand::StablePoll
+            | ScaleCommand::Tare
+            | ScaleCommand::Info
+            | ScaleCommand::Print
+            | ScaleCommand::Clear
+            | ScaleCommand::GrossNet => None,
+        }
+    }
+}
+
+/// Protocolo ECR de CAS: el host envía `ENQ`, la báscula contesta `ACK`,
+/// el host pide el dato con `DC1` y recibe una trama de largo fijo
+/// `SOH STX STA SIGN PESO(6) UNIDAD(2) BCC ETX EOT`.
+pub struct CasEcrProtocol;
+
+const LARGO_CAS: usize = 15;
+
+impl ScaleProtocol for CasEcrProtocol {
+    fn name(&self) -> &'static str {
+        "cas_ecr"
+    }
+
+    fn next_frame(&self, pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+        loop {
+            match pending.first() {
+                None => return None,
+                Some(&ACK) | Some(&NAK) => return Some(pending.drain(..1).collect()),
+                Some(&SOH) => {}
+                Some(_) => {
+                    // Basura previa: avanzar hasta el próximo byte significativo
+                    let inicio = pending
+                        .iter()
+                        .position(|&b| b == SOH || b == ACK || b == NAK)
+                        .unwrap_or(pending.len());
+                    pending.drain(..inicio);
+                    continue;
+                }
+            }
+
+            if pending.len() < LARGO_CAS {
+                return None;
+            }
+            if pending[LARGO_CAS - 1] == EOT {
+                return Some(pending.drain(..LARGO_CAS).collect());
+            }
+            pending.remove(0);
+        }
+    }
+
+    fn parse(&self, frame: &[u8]) -> Option<WeightReading> {
+        if frame.len() != LARGO_CAS || frame[0] != SOH {
+            return None;
+        }
+
+        let (estado, signo) = (frame[2], frame[3]);
+
+        let campo = String::from_utf8_lossy(&frame[4..12]);
+        let (absoluto, decimals, _, unit) = peso_y_unidad(&campo)?;
+        let negative = signo == b'-';
+
+        let range = match estado {
+            b'F' | b'O' => RangeStatus::Overload,
+            _ => RangeStatus::Normal,
+        };
+
+        Some(WeightReading {
+            value: if negative { -absoluto } else { absoluto },
+            decimals,
+            unit,
+            negative,
+            mode: WeightMode::Gross,
+            stable: estado == b'S',
+            range,
+            tare: None,
+            at_zero: absoluto == 0.0,
+        })
+    }
+
+    fn response(&self, frame: &[u8]) -> Option<ScaleResponse> {
+        match frame {
+            [NAK] => Some(ScaleResponse::Error("NAK".to_string())),
+            _ => None,
+        }
+    }
+
+    fn integrity(&self) -> Option<Integrity> {
+        // BCC: XOR de STA ... UNIDAD, seguido de ETX EOT
+        Some(Integrity {
+            checksum: ChecksumKind::Xor,
+            skip_start: 2,
+            skip_end: 2,
+            min_length: Some(LARGO_CAS),
+            max_length: Some(LARGO_CAS),
+            ..Default::default()
+        })
+    }
+
+    fn handshake_reply(&self, frame: &[u8]) -> Option<Vec<u8>> {
+        // ACK al ENQ: pedir el dato de peso
+        match frame {
+            [ACK] => Some(vec![DC1]),
+            _ => None,
+        }
+    }
+
+    fn reports_motion(&self, _frame: &[u8]) -> bool {
+        true
+    }
+
+    fn command(&self, cmd: ScaleCommand) -> Option<Vec<u8>> {
+        match cmd {
+            ScaleCommand::Poll => Some(vec![ENQ]),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nci_peso_y_estado() {
+        let p = NciProtocol;
+
+        let r = p.parse(b"\n  1.250kg\r\n00\r\x03").unwrap();
+        assert_eq!((r.value, r.decimals, r.unit.as_str()), (1.25, 3, "kg"));
+        assert!(r.stable && !r.at_zero);
+        assert_eq!(r.range, RangeStatus::Normal);
+
+        // Byte 1: bit 0 movimiento, bit 1 en cero
+        assert!(!p.parse(b"\n  1.250kg\r\n10\r\x03").unwrap().stable);
+        assert!(p.parse(b"\n  0.000kg\r\n20\r\x03").unwrap().at_zero);
+        assert_eq!(p.parse(b"\n -0.500lb\r\n00\r\x03").unwrap().value, -0.5);
+
+        // Solo estado: lectura únicamente si está fuera de rango (byte 2: bit 0 bajo, bit 1 sobre)
+        assert_eq!(p.parse(b"\n02\r\x03").unwrap().range, RangeStatus::Overload);
+        assert_eq!(p.parse(b"\n01\r\x03").unwrap().range, RangeStatus::Underload);
+        assert_eq!(p.parse(b"\n10\r\x03"), None);
+        // Respuesta al comando S
+        assert_eq!(p.parse(b"\nS02\r\x03").unwrap().range, RangeStatus::Overload);
+        // Bits 4 y 5 en 0: no es una línea de estado
+        assert_eq!(p.parse(b"\n  1.250kg\r\nAB\r\x03"), None);
+
+        assert_eq!(p.response(b"\n?\r\x03"), Some(ScaleResponse::Error("?".to_string())));
+        assert_eq!(p.response(b"\n  1.250kg\r\n00\r\x03"), None);
+
+        let mut pending = b"\n  1.250kg\r\n00\r\x03\n02".to_vec();
+        assert_eq!(p.next_frame(&mut pending).as_deref(), Some(&b"\n  1.250kg\r\n00\r\x03"[..]));
+        assert_eq!(p.next_frame(&mut pending), None);
+    }
+
+    /// `SOH STX STA SIGN PESO(6) UNIDAD(2) BCC ETX EOT`
+    const CAS_ESTABLE: &[u8] = b"\x01\x02S 12.345kg`\x03\x04";
+    const CAS_NEGATIVO_INESTABLE: &[u8] = b"\x01\x02U-01.250kgl\x03\x04";
+    const CAS_SOBRECARGA: &[u8] = b"\x01\x02F 99.999kg}\x03\x04";
+
+    #[test]
+    fn cas_ecr_tramas() {
+        let p = CasEcrProtocol;
+
+        let r = p.parse(CAS_ESTABLE).unwrap();
+        assert_eq!((r.value, r.decimals, r.unit.as_str()), (12.345, 3, "kg"));
+        assert!(r.stable && !r.negative);
+
+        let r = p.parse(CAS_NEGATIVO_INESTABLE).unwrap();
+        assert_eq!(r.value, -1.25);
+        assert!(r.negative && !r.stable);
+
+        assert_eq!(p.parse(CAS_SOBRECARGA).unwrap().range, RangeStatus::Overload);
+
+        // ACK / NAK sueltos y basura previa a SOH
+        let mut pending = [b"x\x06".as_slice(), CAS_ESTABLE, b"\x15"].concat();
+        assert_eq!(p.next_frame(&mut pending).as_deref(), Some(&[ACK][..]));
+        assert_eq!(p.handshake_reply(&[ACK]), Some(vec![DC1]));
+        assert_eq!(p.next_frame(&mut pending).as_deref(), Some(CAS_ESTABLE));
+        let nak = p.next_frame(&mut pending).unwrap();
+        assert_eq!(p.response(&nak), Some(ScaleResponse::Error("NAK".to_string())));
+    }
+
+    #[test]
+    fn cas_ecr_bcc() {
+        // El BCC cubre STA ... UNIDAD: sin SOH STX al inicio ni ETX EOT al final
+        let integrity = CasEcrProtocol.integrity().unwrap();
+        for trama in [CAS_ESTABLE, CAS_NEGATIVO_INESTABLE, CAS_SOBRECARGA] {
+            assert_eq!(integrity.verificar(trama), Ok(()));
+        }
+        let mut alterada = CAS_ESTABLE.to_vec();
+        alterada[2] = b'U';
+        assert_eq!(integrity.verificar(&alterada), Err("checksum"));
+        assert_eq!(integrity.verificar(&CAS_ESTABLE[..14]), Err("largo_minimo"));
+    }
+}
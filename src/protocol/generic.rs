@@ -0,0 +1,47 @@
+use crate::filter::{default_rules, FilterRule};
+use crate::weight::{informa_movimiento, parse_frame, WeightReading};
+
+use super::{ScaleCommand, ScaleProtocol};
+
+/// Protocolo original del puente: tramas ASCII terminadas en 0x0D y
+/// solicitadas con `W`. El descarte de ruido queda a cargo de `[[filter]]`.
+pub struct GenericProtocol;
+
+impl ScaleProtocol for GenericProtocol {
+    fn name(&self) -> &'static str {
+        "generic"
+    }
+
+    fn next_frame(&self, pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+        let pos = pending.iter().position(|&b| b == 0x0D)?;
+        Some(pending.drain(..=pos).collect())
+    }
+
+    fn default_filters(&self) -> Vec<FilterRule> {
+        default_rules()
+    }
+
+    fn parse(&self, frame: &[u8]) -> Option<WeightReading> {
+        parse_frame(frame)
+    }
+
+    /// Solo las tramas con indicador de estado (`ST`, `US`...) informan movimiento.
+    fn reports_motion(&self, frame: &[u8]) -> bool {
+        informa_movimiento(frame)
+    }
+
+    fn command(&self, cmd: ScaleCommand) -> Option<Vec<u8>> {
+        match cmd {
+            ScaleCommand::Poll => Some(b"W".to_vec()),
+            // Letras habituales en indicadores ASCII
+            ScaleCommand::Tare => Some(b"T".to_vec()),
+            ScaleCommand::Zero => Some(b"Z".to_vec()),
+            ScaleCommand::Print => Some(b"P".to_vec()),
+            ScaleCommand::Clear => Some(b"C".to_vec()),
+            ScaleCommand::StablePoll
+            | ScaleCommand::Info
+            | ScaleCommand::Diagnostics
+            | ScaleCommand::GrossNet => None,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+mod ecr;
+mod generic;
+mod sics;
+mod sma;
+mod toledo;
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+
+use crate::config::Config;
+use crate::filter::FilterRule;
+use crate::integrity::Integrity;
+use crate::weight::WeightReading;
+
+pub use ecr::{CasEcrProtocol, NciProtocol};
+pub use generic::GenericProtocol;
+pub use sics::SicsProtocol;
+pub use sma::SmaProtocol;
+pub use toledo::ToledoProtocol;
+
+/// Comandos que el puente puede enviar a la báscula.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleCommand {
+    /// Solicitar el peso actual
+    Poll,
+    /// Solicitar el peso cuando la báscula lo reporte estable
+    StablePoll,
+    Tare,
+    Zero,
+    /// Información de la báscula (fabricante, versión)
+    Info,
+    /// Autodiagnóstico
+    Diagnostics,
+    /// Imprimir el ticket en la impresora del indicador
+    Print,
+    /// Borrar la tara
+    Clear,
+    /// Alternar la visualización entre bruto y neto
+    GrossNet,
+}
+
+/// Respuesta de la báscula a un comando (no es una lectura de peso).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScaleResponse {
+    /// Comando aceptado, con el código de respuesta de la báscula
+    Ack(String),
+    /// Comando rechazado o error de comunicación reportado por la báscula
+    Error(String),
+}
+
+/// Driver de protocolo de una marca/modelo de báscula: cómo se delimitan
+/// las tramas, cuáles se descartan, cómo se interpretan y qué bytes se
+/// envían para cada comando.
+pub trait ScaleProtocol: Send + Sync {
+    /// Nombre del protocolo tal como se usa en `protocol = "..."`
+    fn name(&self) -> &'static str;
+
+    /// Extrae del buffer pendiente la siguiente trama completa, si la hay.
+    fn next_frame(&self, pending: &mut Vec<u8>) -> Option<Vec<u8>>;
+
+    /// Indica si una trama completa debe llegar a la caché.
+    fn is_relevant(&self, _frame: &[u8]) -> bool {
+        true
+    }
+
+    /// Interpreta una trama relevante como lectura de peso.
+    fn parse(&self, frame: &[u8]) -> Option<WeightReading>;
+
+    /// Reconoce tramas que son respuesta a un comando (confirmación o error).
+    fn response(&self, _frame: &[u8]) -> Option<ScaleResponse> {
+        None
+    }
+
+    /// Reglas de filtro del protocolo, usadas si el archivo no define `[[filter]]`.
+    fn default_filters(&self) -> Vec<FilterRule> {
+        Vec::new()
+    }
+
+    /// Validación de integridad propia del protocolo (checksum, largo...).
+    fn integrity(&self) -> Option<Integrity> {
+        None
+    }
+
+    /// Bytes que el lector debe enviar de inmediato al recibir la trama
+    /// (handshakes como ENQ/ACK/DC1).
+    fn handshake_reply(&self, _frame: &[u8]) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// Bytes a enviar por serial para el comando, o `None` si el protocolo no lo soporta.
+    fn command(&self, cmd: ScaleCommand) -> Option<Vec<u8>>;
+
+    /// Indica si la trama informa movimiento, de modo que `stable` es confiable.
+    fn reports_motion(&self, _frame: &[u8]) -> bool {
+        false
+    }
+
+    /// Indica si la báscula confirma el comando con una respuesta propia
+    /// (ver `response`); si no, solo se puede informar que fue enviado.
+    fn acknowledges(&self, _cmd: ScaleCommand) -> bool {
+        false
+    }
+
+    /// Indica si `respuesta` puede corresponder a `cmd`, para no atribuirle la
+    /// confirmación de otro comando enviado en el mismo intervalo.
+    fn responds_to(&self, _cmd: ScaleCommand, _respuesta: &ScaleResponse) -> bool {
+        true
+    }
+}
+
+/// Crea el driver de protocolo indicado por `protocol` en la configuración.
+pub fn from_config(config: &Config) -> Result<Arc<dyn ScaleProtocol>> {
+    match config.protocol.to_lowercase().as_str() {
+        "generic" => Ok(Arc::new(GenericProtocol)),
+        "toledo" => Ok(Arc::new(ToledoProtocol { checksum: config.toledo_checksum })),
+        "sics" => Ok(Arc::new(SicsProtocol)),
+        "sma" => Ok(Arc::new(SmaProtocol)),
+        "nci" => Ok(Arc::new(NciProtocol)),
+        "cas_ecr" => Ok(Arc::new(CasEcrProtocol)),
+        otro => bail!("Protocolo de báscula desconocido: '{}'", otro),
+    }
+}
+
+/// Describe en texto los comandos soportados por el protocolo (para logs).
+pub fn describe_commands(protocol: &dyn ScaleProtocol) -> String {
+    [
+        ScaleCommand::Poll,
+        ScaleCommand::StablePoll,
+        ScaleCommand::Tare,
+        ScaleCommand::Zero,
+        ScaleCommand::Info,
+        ScaleCommand::Diagnostics,
+        ScaleCommand::Print,
+        ScaleCommand::Clear,
+        ScaleCommand::GrossNet,
+    ]
+        .iter()
+        .map(|cmd| {
+            let soportado = if protocol.command(*cmd).is_some() { "sí" } else { "no" };
+            format!("{:?}: {}", cmd, soportado)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,155 @@
+use crate::weight::{RangeStatus, WeightMode, WeightReading};
+
+use super::{ScaleCommand, ScaleProtocol, ScaleResponse};
+
+/// MT-SICS (Mettler Toledo Standard Interface Command Set): comandos y
+/// respuestas en líneas ASCII terminadas en CR LF, p. ej. `S S     12.345 g`.
+pub struct SicsProtocol;
+
+/// Separa una respuesta SICS en comando, estado y resto de campos.
+fn campos(frame: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(frame)
+        .split_whitespace()
+        .map(|c| c.to_string())
+        .collect()
+}
+
+impl ScaleProtocol for SicsProtocol {
+    fn name(&self) -> &'static str {
+        "sics"
+    }
+
+    fn next_frame(&self, pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+        let pos = pending.iter().position(|&b| b == b'\n')?;
+        Some(pending.drain(..=pos).collect())
+    }
+
+    fn is_relevant(&self, frame: &[u8]) -> bool {
+        !campos(frame).is_empty()
+    }
+
+    fn parse(&self, frame: &[u8]) -> Option<WeightReading> {
+        let campos = campos(frame);
+        let (cmd, estado) = (campos.first()?, campos.get(1)?);
+        if cmd != "S" && cmd != "SI" {
+            return None;
+        }
+
+        let range = match estado.as_str() {
+            "S" | "D" => RangeStatus::Normal,
+            "+" => RangeStatus::Overload,
+            "-" => RangeStatus::Underload,
+            _ => return None,
+        };
+
+        let (value, decimals, negative, unit) = match campos.get(2) {
+            Some(valor) if range == RangeStatus::Normal => {
+                let decimals = valor.split('.').nth(1).map(|d| d.len() as u8).unwrap_or(0);
+                let unit = campos.get(3).cloned().unwrap_or_default();
+                (valor.parse::<f64>().ok()?, decimals, valor.starts_with('-'), unit)
+            }
+            _ => (0.0, 0, false, String::new()),
+        };
+
+        Some(WeightReading {
+            value,
+            decimals,
+            unit,
+            negative,
+            mode: WeightMode::Gross,
+            stable: estado == "S",
+            range,
+            tare: None,
+            at_zero: false,
+        })
+    }
+
+    fn response(&self, frame: &[u8]) -> Option<ScaleResponse> {
+        let campos = campos(frame);
+        let cmd = campos.first()?.as_str();
+        let estado = campos.get(1).map(|s| s.as_str()).unwrap_or("");
+        let codigo = format!("{} {}", cmd, estado).trim().to_string();
+
+        match (cmd, estado) {
+            ("ES" | "ET" | "EL", _) => Some(ScaleResponse::Error(cmd.to_string())),
+            // "S I": comando no ejecutable en este momento (báscula ocupada)
+            ("S" | "SI", "I") => Some(ScaleResponse::Error(codigo)),
+            ("T", "S") | ("Z" | "TAC" | "I4", "A") => Some(ScaleResponse::Ack(codigo)),
+            ("T" | "Z" | "TAC", _) => Some(ScaleResponse::Error(codigo)),
+            _ => None,
+        }
+    }
+
+    fn reports_motion(&self, _frame: &[u8]) -> bool {
+        true
+    }
+
+    fn command(&self, cmd: ScaleCommand) -> Option<Vec<u8>> {
+        let bytes: &[u8] = match cmd {
+            ScaleCommand::Poll => b"SI\r\n",
+            ScaleCommand::StablePoll => b"S\r\n",
+            ScaleCommand::Tare => b"T\r\n",
+            ScaleCommand::Zero => b"Z\r\n",
+            // "I4" devuelve el número de serie
+            ScaleCommand::Info => b"I4\r\n",
+            ScaleCommand::Clear => b"TAC\r\n",
+            // Tecla bruto/neto habitual de los terminales; MT-SICS no la estandariza
+            ScaleCommand::GrossNet => b"G\r\n",
+            ScaleCommand::Diagnostics | ScaleCommand::Print => return None,
+        };
+        Some(bytes.to_vec())
+    }
+
+    fn acknowledges(&self, cmd: ScaleCommand) -> bool {
+        matches!(cmd, ScaleCommand::Tare | ScaleCommand::Zero | ScaleCommand::Clear | ScaleCommand::Info)
+    }
+
+    fn responds_to(&self, cmd: ScaleCommand, respuesta: &ScaleResponse) -> bool {
+        // La respuesta empieza con el mismo identificador que el comando ("T S", "TAC A"...),
+        // salvo "SI", que se responde como "S" ("S S", "S D", "S I");
+        // ES, ET y EL son errores de cualquier comando
+        let codigo = match respuesta {
+            ScaleResponse::Ack(codigo) | ScaleResponse::Error(codigo) => codigo,
+        };
+        let id = codigo.split_whitespace().next().unwrap_or_default();
+        let enviado = self.command(cmd).map(|b| String::from_utf8_lossy(&b).trim().to_string());
+        let peso = matches!(cmd, ScaleCommand::Poll | ScaleCommand::StablePoll) && id == "S";
+        matches!(id, "ES" | "ET" | "EL") || peso || enviado.as_deref() == Some(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn respuestas_a_si() {
+        let p = SicsProtocol;
+
+        // "S S" y "S D" son tramas de peso, no respuestas
+        assert_eq!(p.response(b"S S      12.345 g\r\n"), None);
+        let estable = p.parse(b"S S      12.345 g\r\n").unwrap();
+        assert!(estable.stable);
+        assert_eq!((estable.value, estable.decimals, estable.unit.as_str()), (12.345, 3, "g"));
+        assert_eq!(p.response(b"S D      12.340 g\r\n"), None);
+        assert!(!p.parse(b"S D      12.340 g\r\n").unwrap().stable);
+
+        // "S I" (báscula ocupada) es la respuesta de error a "SI" y a "S"
+        let ocupada = p.response(b"S I\r\n").unwrap();
+        assert_eq!(ocupada, ScaleResponse::Error("S I".to_string()));
+        assert!(p.responds_to(ScaleCommand::Poll, &ocupada));
+        assert!(p.responds_to(ScaleCommand::StablePoll, &ocupada));
+        assert!(!p.responds_to(ScaleCommand::Tare, &ocupada));
+    }
+
+    #[test]
+    fn respuestas_a_comandos() {
+        let p = SicsProtocol;
+        let tara = p.response(b"T S      10.000 g\r\n").unwrap();
+        assert!(p.responds_to(ScaleCommand::Tare, &tara));
+        assert!(!p.responds_to(ScaleCommand::Zero, &tara));
+        let error = p.response(b"ES\r\n").unwrap();
+        assert!(p.responds_to(ScaleCommand::Zero, &error));
+        assert!(p.responds_to(ScaleCommand::Poll, &error));
+    }
+}
@@ -0,0 +1,189 @@
+use crate::weight::{RangeStatus, WeightMode, WeightReading};
+
+use super::{ScaleCommand, ScaleProtocol, ScaleResponse};
+
+const LF: u8 = 0x0A;
+const CR: u8 = 0x0D;
+/// `s r n m f` + peso (10) + unidad (3)
+const LARGO_PESO: usize = 18;
+
+/// Protocolo estándar SMA (Scale Manufacturers Association):
+/// solicitudes `<LF>x<CR>` y respuestas
+/// `<LF><s><r><n><m><f><xxxxxx.xxx><uuu><CR>`.
+pub struct SmaProtocol;
+
+/// Contenido de la trama sin `<LF>` inicial ni `<CR>`/`<LF>` finales.
+fn cuerpo(frame: &[u8]) -> &[u8] {
+    let inicio = frame.iter().position(|&b| b != LF && b != CR).unwrap_or(frame.len());
+    let fin = frame.iter().rposition(|&b| b != LF && b != CR).map(|p| p + 1).unwrap_or(inicio);
+    &frame[inicio..fin.max(inicio)]
+}
+
+impl ScaleProtocol for SmaProtocol {
+    fn name(&self) -> &'static str {
+        "sma"
+    }
+
+    fn next_frame(&self, pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+        let pos = pending.iter().position(|&b| b == CR)?;
+        Some(pending.drain(..=pos).collect())
+    }
+
+    fn is_relevant(&self, frame: &[u8]) -> bool {
+        !cuerpo(frame).is_empty()
+    }
+
+    fn parse(&self, frame: &[u8]) -> Option<WeightReading> {
+        let cuerpo = cuerpo(frame);
+        if cuerpo.len() != LARGO_PESO {
+            return None;
+        }
+
+        let (estado, modo, movimiento) = (cuerpo[0], cuerpo[2], cuerpo[3]);
+        let texto_peso = std::str::from_utf8(&cuerpo[5..15]).ok()?.trim().replace(' ', "");
+        let unit = std::str::from_utf8(&cuerpo[15..18]).ok()?.trim().to_lowercase();
+
+        let range = match estado {
+            b'O' => RangeStatus::Overload,
+            b'U' => RangeStatus::Underload,
+            _ => RangeStatus::Normal,
+        };
+
+        let value = if texto_peso.is_empty() || range != RangeStatus::Normal {
+            0.0
+        } else {
+            texto_peso.parse::<f64>().ok()?
+        };
+        let decimals = texto_peso.split('.').nth(1).map(|d| d.len() as u8).unwrap_or(0);
+
+        let mode = match modo.to_ascii_uppercase() {
+            b'N' => WeightMode::Net,
+            b'T' => WeightMode::Tare,
+            _ => WeightMode::Gross,
+        };
+
+        Some(WeightReading {
+            value,
+            decimals,
+            unit,
+            negative: texto_peso.starts_with('-'),
+            mode,
+            stable: movimiento != b'M',
+            range,
+            tare: None,
+            at_zero: estado == b'Z',
+        })
+    }
+
+    fn response(&self, frame: &[u8]) -> Option<ScaleResponse> {
+        let cuerpo = cuerpo(frame);
+        let texto = String::from_utf8_lossy(cuerpo).to_string();
+
+        match cuerpo.len() {
+            LARGO_PESO => None,
+            // Comando no reconocido por la báscula
+            1 if cuerpo[0] == b'?' => Some(ScaleResponse::Error("?".to_string())),
+            // Diagnóstico: <r><e><c><m>, espacio = sin falla
+            4 if cuerpo.iter().all(|b| b" RECM".contains(b)) => {
+                if cuerpo.iter().all(|&b| b == b' ') {
+                    Some(ScaleResponse::Ack("D".to_string()))
+                } else {
+                    Some(ScaleResponse::Error(format!("D {}", texto)))
+                }
+            }
+            // Información ("SMA:2/1.0", "MFG:...", etc.)
+            _ if texto.contains(':') => Some(ScaleResponse::Ack(texto)),
+            _ => None,
+        }
+    }
+
+    fn reports_motion(&self, _frame: &[u8]) -> bool {
+        true
+    }
+
+    fn command(&self, cmd: ScaleCommand) -> Option<Vec<u8>> {
+        let letra = match cmd {
+            ScaleCommand::Poll => b'W',
+            ScaleCommand::StablePoll | ScaleCommand::Print | ScaleCommand::Clear => return None,
+            // Alterna la visualización entre bruto y neto
+            ScaleCommand::GrossNet => b'G',
+            ScaleCommand::Tare => b'T',
+            ScaleCommand::Zero => b'Z',
+            ScaleCommand::Info => b'I',
+            ScaleCommand::Diagnostics => b'D',
+        };
+        Some(vec![LF, letra, CR])
+    }
+
+    fn acknowledges(&self, cmd: ScaleCommand) -> bool {
+        matches!(cmd, ScaleCommand::Info | ScaleCommand::Diagnostics)
+    }
+
+    fn responds_to(&self, cmd: ScaleCommand, respuesta: &ScaleResponse) -> bool {
+        match respuesta {
+            // "?" rechaza cualquier comando
+            ScaleResponse::Error(codigo) if codigo == "?" => true,
+            ScaleResponse::Ack(codigo) | ScaleResponse::Error(codigo) if codigo.contains(':') => cmd == ScaleCommand::Info,
+            _ => cmd == ScaleCommand::Diagnostics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Respuesta a `<LF>W<CR>`: `<LF><s><r><n><m><f><xxxxxx.xxx><uuu><CR>`
+    fn trama(srnmf: &str, peso: &str, unidad: &str) -> Vec<u8> {
+        assert_eq!((srnmf.len(), peso.len(), unidad.len()), (5, 10, 3));
+        [&[LF], srnmf.as_bytes(), peso.as_bytes(), unidad.as_bytes(), &[CR]].concat()
+    }
+
+    #[test]
+    fn tramas_de_peso() {
+        let p = SmaProtocol;
+
+        let estable = trama(" 1G  ", "    12.345", "kg ");
+        assert_eq!(p.response(&estable), None);
+        let r = p.parse(&estable).unwrap();
+        assert_eq!((r.value, r.decimals, r.unit.as_str()), (12.345, 3, "kg"));
+        assert!(r.stable && !r.negative && !r.at_zero);
+        assert_eq!((r.mode, r.range), (WeightMode::Gross, RangeStatus::Normal));
+
+        let r = p.parse(&trama(" 1NM ", "     -2.50", "lb ")).unwrap();
+        assert_eq!((r.value, r.decimals, r.unit.as_str()), (-2.5, 2, "lb"));
+        assert!(!r.stable && r.negative);
+        assert_eq!(r.mode, WeightMode::Net);
+
+        let r = p.parse(&trama("Z1G  ", "     0.000", "kg ")).unwrap();
+        assert!(r.at_zero);
+
+        let r = p.parse(&trama("O1G  ", "^^^^^^^^^^", "kg ")).unwrap();
+        assert_eq!((r.value, r.range), (0.0, RangeStatus::Overload));
+        let r = p.parse(&trama("U1G  ", "__________", "kg ")).unwrap();
+        assert_eq!(r.range, RangeStatus::Underload);
+    }
+
+    #[test]
+    fn respuestas_por_comando() {
+        let p = SmaProtocol;
+
+        // "?" rechaza cualquier comando
+        let rechazo = p.response(b"\n?\r").unwrap();
+        assert_eq!(rechazo, ScaleResponse::Error("?".to_string()));
+        assert!(p.responds_to(ScaleCommand::Tare, &rechazo));
+        assert!(p.responds_to(ScaleCommand::Info, &rechazo));
+
+        let info = p.response(b"\nSMA:2/1.0\r").unwrap();
+        assert_eq!(info, ScaleResponse::Ack("SMA:2/1.0".to_string()));
+        assert!(p.responds_to(ScaleCommand::Info, &info));
+        assert!(!p.responds_to(ScaleCommand::Diagnostics, &info));
+
+        let sin_falla = p.response(b"\n    \r").unwrap();
+        assert_eq!(sin_falla, ScaleResponse::Ack("D".to_string()));
+        let falla = p.response(b"\nR E \r").unwrap();
+        assert_eq!(falla, ScaleResponse::Error("D R E ".to_string()));
+        assert!(p.responds_to(ScaleCommand::Diagnostics, &falla));
+        assert!(!p.responds_to(ScaleCommand::Info, &falla));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::integrity::{ChecksumKind, Integrity};
+use crate::weight::{RangeStatus, WeightMode, WeightReading};
+
+use super::{ScaleCommand, ScaleProtocol};
+
+const STX: u8 = 0x02;
+const CR: u8 = 0x0D;
+/// STX + 3 palabras de estado + 6 dígitos de peso + 6 dígitos de tara + CR
+const LARGO_TRAMA: usize = 17;
+
+/// Salida continua Mettler Toledo:
+/// `STX SWA SWB SWC PPPPPP TTTTTT CR [checksum]`.
+pub struct ToledoProtocol {
+    /// La báscula agrega el byte de checksum después del CR
+    pub checksum: bool,
+}
+
+impl ToledoProtocol {
+    fn largo(&self) -> usize {
+        if self.checksum {
+            LARGO_TRAMA + 1
+        } else {
+            LARGO_TRAMA
+        }
+    }
+}
+
+fn campo_numerico(campo: &[u8]) -> Option<u32> {
+    let texto = std::str::from_utf8(campo).ok()?.trim();
+    if texto.is_empty() {
+        return Some(0);
+    }
+    texto.parse().ok()
+}
+
+impl ScaleProtocol for ToledoProtocol {
+    fn name(&self) -> &'static str {
+        "toledo"
+    }
+
+    fn next_frame(&self, pending: &mut Vec<u8>) -> Option<Vec<u8>> {
+        loop {
+            // Descartar basura previa al STX
+            match pending.iter().position(|&b| b == STX) {
+                Some(inicio) => {
+                    pending.drain(..inicio);
+                }
+                None => {
+                    pending.clear();
+                    return None;
+                }
+            }
+
+            if pending.len() < self.largo() {
+                return None;
+            }
+
+            if pending[LARGO_TRAMA - 1] == CR {
+                return Some(pending.drain(..self.largo()).collect());
+            }
+
+            // STX espurio: resincronizar a partir del siguiente
+            pending.remove(0);
+        }
+    }
+
+    fn is_relevant(&self, frame: &[u8]) -> bool {
+        // SWB bit 6: la báscula está encendiendo y aún no reporta peso
+        frame.len() >= self.largo() && frame[2] & 0x40 == 0
+    }
+
+    fn parse(&self, frame: &[u8]) -> Option<WeightReading> {
+        if frame.len() < LARGO_TRAMA || frame[0] != STX {
+            return None;
+        }
+        let (swa, swb, swc) = (frame[1], frame[2], frame[3]);
+        let bruto = campo_numerico(&frame[4..10])?;
+        let tara = campo_numerico(&frame[10..16])?;
+
+        // SWA bits 0-2: posición del punto decimal (0 = XX00 ... 7 = 0.00000X)
+        let punto = (swa & 0x07) as i32;
+        let factor = 10f64.powi(2 - punto);
+        let decimals = (punto - 2).max(0) as u8;
+
+        let negative = swb & 0x02 != 0;
+        let fuera_de_rango = swb & 0x04 != 0;
+        let absoluto = bruto as f64 * factor;
+
+        let unit = match swc & 0x07 {
+            0 if swb & 0x10 != 0 => "kg",
+            0 => "lb",
+            1 => "g",
+            2 => "t",
+            3 => "oz",
+            4 => "ozt",
+            5 => "dwt",
+            6 => "ton",
+            _ => "",
+        };
+
+        let range = match (fuera_de_rango, negative) {
+            (false, _) => RangeStatus::Normal,
+            (true, false) => RangeStatus::Overload,
+            (true, true) => RangeStatus::Underload,
+        };
+
+        Some(WeightReading {
+            value: if negative { -absoluto } else { absoluto },
+            decimals,
+            unit: unit.to_string(),
+            negative,
+            mode: if swb & 0x01 != 0 { WeightMode::Net } else { WeightMode::Gross },
+            stable: swb & 0x08 == 0,
+            range,
+            tare: Some(tara as f64 * factor),
+            at_zero: false,
+        })
+    }
+
+    fn integrity(&self) -> Option<Integrity> {
+        // Complemento a dos de la suma en 7 bits de STX ... CR
+        self.checksum.then(|| Integrity {
+            checksum: ChecksumKind::TwosComplement7,
+            min_length: Some(LARGO_TRAMA + 1),
+            ..Default::default()
+        })
+    }
+
+    fn reports_motion(&self, _frame: &[u8]) -> bool {
+        true
+    }
+
+    fn command(&self, cmd: ScaleCommand) -> Option<Vec<u8>> {
+        match cmd {
+            // Salida continua: no hay solicitud de peso
+            ScaleCommand::Poll | ScaleCommand::StablePoll => None,
+            ScaleCommand::Tare => Some(b"T".to_vec()),
+            ScaleCommand::Zero => Some(b"Z".to_vec()),
+            ScaleCommand::Print => Some(b"P".to_vec()),
+            ScaleCommand::Clear => Some(b"C".to_vec()),
+            // Alterna la visualización entre bruto y neto
+            ScaleCommand::GrossNet => Some(b"G".to_vec()),
+            ScaleCommand::Info | ScaleCommand::Diagnostics => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CON_CHECKSUM: ToledoProtocol = ToledoProtocol { checksum: true };
+
+    /// Tramas de salida continua de 18 bytes (con checksum)
+    const BRUTO_KG: &[u8] = b"\x0240 001234000000\r#";
+    const NEGATIVO_EN_MOVIMIENTO: &[u8] = b"\x024: 000500000000\r\x1e";
+    const NETO_CON_TARA: &[u8] = b"\x0241 001000000250\r$";
+    const GRAMOS: &[u8] = b"\x023 !000123000000\r7";
+    const SOBRECARGA: &[u8] = b"\x0224 999999000000\ru";
+
+    #[test]
+    fn palabras_de_estado() {
+        let r = CON_CHECKSUM.parse(BRUTO_KG).unwrap();
+        assert_eq!((r.value, r.decimals, r.unit.as_str()), (12.34, 2, "kg"));
+        assert!(r.stable && !r.negative);
+        assert_eq!((r.mode, r.range, r.tare), (WeightMode::Gross, RangeStatus::Normal, Some(0.0)));
+
+        let r = CON_CHECKSUM.parse(NEGATIVO_EN_MOVIMIENTO).unwrap();
+        assert_eq!(r.value, -5.0);
+        assert!(r.negative && !r.stable);
+        assert_eq!(r.range, RangeStatus::Normal);
+
+        let r = CON_CHECKSUM.parse(NETO_CON_TARA).unwrap();
+        assert_eq!((r.value, r.mode, r.tare), (10.0, WeightMode::Net, Some(2.5)));
+
+        // SWA 3: un decimal; SWC 1: gramos
+        let r = CON_CHECKSUM.parse(GRAMOS).unwrap();
+        assert_eq!((r.value, r.decimals, r.unit.as_str()), (12.3, 1, "g"));
+
+        // SWA 2: sin decimales; SWB bit 2 sin signo: sobrecarga
+        let r = CON_CHECKSUM.parse(SOBRECARGA).unwrap();
+        assert_eq!((r.value, r.decimals, r.range), (999999.0, 0, RangeStatus::Overload));
+    }
+
+    #[test]
+    fn checksum_complemento_a_dos() {
+        let integrity = CON_CHECKSUM.integrity().unwrap();
+        for trama in [BRUTO_KG, NEGATIVO_EN_MOVIMIENTO, NETO_CON_TARA, GRAMOS, SOBRECARGA] {
+            assert_eq!(integrity.verificar(trama), Ok(()));
+        }
+        let mut alterada = BRUTO_KG.to_vec();
+        alterada[9] = b'5';
+        assert_eq!(integrity.verificar(&alterada), Err("checksum"));
+        assert_eq!(integrity.verificar(&BRUTO_KG[..17]), Err("largo_minimo"));
+        assert!(ToledoProtocol { checksum: false }.integrity().is_none());
+    }
+
+    #[test]
+    fn separa_tramas_del_flujo() {
+        let mut pending = b"12\r".to_vec();
+        pending.extend_from_slice(BRUTO_KG);
+        pending.extend_from_slice(&NETO_CON_TARA[..5]);
+        assert_eq!(CON_CHECKSUM.next_frame(&mut pending).as_deref(), Some(BRUTO_KG));
+        assert_eq!(CON_CHECKSUM.next_frame(&mut pending), None);
+        pending.extend_from_slice(&NETO_CON_TARA[5..]);
+        assert_eq!(CON_CHECKSUM.next_frame(&mut pending).as_deref(), Some(NETO_CON_TARA));
+        assert!(pending.is_empty());
+    }
+}
@@ -0,0 +1,223 @@
+use serde::Serialize;
+
+use crate::cache::Trama;
+use crate::command::Resultado;
+use crate::plantilla::{Plantillas, Valores};
+use crate::poll::{ResultadoComando, ResultadoPoll};
+
+/// Formato de las respuestas de una conexión TCP, elegido con `FORMAT`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Formato {
+    /// Bytes crudos de la báscula y mensajes de texto (comportamiento histórico)
+    #[default]
+    Texto,
+    /// Una línea JSON por respuesta
+    Json,
+}
+
+impl Formato {
+    /// Interpreta el argumento de `FORMAT JSON|TEXT`.
+    pub fn parse(arg: &str) -> Option<Self> {
+        match arg.to_uppercase().as_str() {
+            "JSON" => Some(Formato::Json),
+            "TEXT" | "RAW" => Some(Formato::Texto),
+            _ => None,
+        }
+    }
+}
+
+/// Código de error informado al cliente.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum CodigoError {
+    /// No hay dato vigente en la caché
+    NoData,
+    /// La báscula no respondió a tiempo
+    Timeout,
+    /// El peso no se estabilizó a tiempo
+    NotStable,
+    /// Línea o argumentos no reconocidos
+    InvalidCommand,
+    /// La báscula rechazó el pedido (el código va en `detail`)
+    ScaleError,
+    /// La báscula no confirmó el comando
+    NoAck,
+    /// El protocolo no tiene secuencia para el comando
+    NotSupported,
+}
+
+/// Cómo se escriben las respuestas en una conexión.
+pub struct Estilo<'a> {
+    pub formato: Formato,
+    pub scale_id: &'a str,
+    /// Plantillas de texto del puerto de la conexión
+    pub plantillas: &'a Plantillas,
+}
+
+/// Respuesta a un comando TCP, independiente del formato en que se envía.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Respuesta<'a> {
+    /// Trama de la báscula
+    Dato(&'a Trama),
+    /// Comando aceptado (`OK`, `SENT`)
+    Hecho(&'a str),
+    /// Error con el código propio de la báscula, si lo hay
+    Error(CodigoError, Option<&'a str>),
+}
+
+/// Línea JSON enviada al cliente; los campos que no aplican se omiten.
+#[derive(Serialize)]
+struct LineaJson<'a> {
+    ok: bool,
+    scale_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    raw: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    unit: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    age_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    result: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<CodigoError>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<&'a str>,
+}
+
+impl<'a> LineaJson<'a> {
+    fn vacia(ok: bool, scale_id: &'a str) -> Self {
+        Self {
+            ok,
+            scale_id,
+            raw: None,
+            value: None,
+            unit: None,
+            stable: None,
+            timestamp: None,
+            age_ms: None,
+            result: None,
+            error: None,
+            detail: None,
+        }
+    }
+}
+
+impl Respuesta<'_> {
+    /// Bytes a escribir en la conexión según su estilo.
+    pub fn bytes(&self, estilo: &Estilo) -> Vec<u8> {
+        match estilo.formato {
+            Formato::Texto => self.texto(estilo),
+            Formato::Json => {
+                let mut linea = self.json(estilo.scale_id).into_bytes();
+                linea.push(b'\n');
+                linea
+            }
+        }
+    }
+
+    /// Respuesta como objeto JSON en una sola línea (sin salto final), para
+    /// las interfaces que no usan plantillas (HTTP, WebSocket, MQTT).
+    pub fn json(&self, scale_id: &str) -> String {
+        serde_json::to_string(&self.linea_json(scale_id)).unwrap_or_default()
+    }
+
+    /// Respuesta de texto: la plantilla configurada o, si no hay, la histórica.
+    fn texto(&self, estilo: &Estilo) -> Vec<u8> {
+        let p = estilo.plantillas;
+        let (plantilla, trama, detail) = match self {
+            Respuesta::Dato(trama) => (&p.data, Some(*trama), None),
+            Respuesta::Hecho(_) => (&None, None, None),
+            Respuesta::Error(codigo, detail) => {
+                let plantilla = match codigo {
+                    CodigoError::NoData => &p.no_data,
+                    CodigoError::Timeout => &p.timeout,
+                    CodigoError::NotStable => &p.not_stable,
+                    CodigoError::InvalidCommand => &p.invalid_command,
+                    CodigoError::ScaleError => &p.scale_error,
+                    CodigoError::NoAck => &p.no_ack,
+                    CodigoError::NotSupported => &p.not_supported,
+                };
+                (plantilla, None, *detail)
+            }
+        };
+        if let Some(plantilla) = plantilla {
+            return plantilla.render(&Valores { trama, scale_id: estilo.scale_id, detail });
+        }
+
+        let texto = match self {
+            Respuesta::Dato(trama) => return trama.data.clone(),
+            Respuesta::Hecho(resultado) => format!("{}\n", resultado),
+            Respuesta::Error(CodigoError::ScaleError, codigo) => format!("ERROR {}\n", codigo.unwrap_or_default()),
+            Respuesta::Error(CodigoError::NoData, _) => "NO DATA\n".to_string(),
+            Respuesta::Error(CodigoError::Timeout, _) => "W_TIMEOUT\n".to_string(),
+            Respuesta::Error(CodigoError::NotStable, _) => "NOT_STABLE\n".to_string(),
+            Respuesta::Error(CodigoError::InvalidCommand, _) => "Comando invalido\n".to_string(),
+            Respuesta::Error(CodigoError::NoAck, _) => "NO_ACK\n".to_string(),
+            Respuesta::Error(CodigoError::NotSupported, _) => "NOT_SUPPORTED\n".to_string(),
+        };
+        texto.into_bytes()
+    }
+
+    fn linea_json<'b>(&'b self, scale_id: &'b str) -> LineaJson<'b> {
+        match self {
+            Respuesta::Dato(trama) => {
+                let lectura = trama.lectura.as_ref();
+                LineaJson {
+                    raw: Some(String::from_utf8_lossy(&trama.data).into_owned()),
+                    value: lectura.map(|r| r.value),
+                    unit: lectura.map(|r| r.unit.as_str()).filter(|u| !u.is_empty()),
+                    stable: lectura.map(|r| r.stable),
+                    timestamp: Some(trama.fecha.to_rfc3339_opts(chrono::SecondsFormat::Millis, false)),
+                    age_ms: Some(trama.recibida.elapsed().as_millis() as u64),
+                    ..LineaJson::vacia(true, scale_id)
+                }
+            }
+            Respuesta::Hecho(resultado) => LineaJson { result: Some(resultado), ..LineaJson::vacia(true, scale_id) },
+            Respuesta::Error(codigo, detalle) => LineaJson {
+                error: Some(*codigo),
+                detail: *detalle,
+                ..LineaJson::vacia(false, scale_id)
+            },
+        }
+    }
+}
+
+impl<'a> From<&'a Resultado> for Respuesta<'a> {
+    fn from(resultado: &'a Resultado) -> Self {
+        match resultado {
+            Resultado::Ok => Respuesta::Hecho("OK"),
+            Resultado::Enviado => Respuesta::Hecho("SENT"),
+            Resultado::Error(codigo) => Respuesta::Error(CodigoError::ScaleError, Some(codigo)),
+            Resultado::SinConfirmacion => Respuesta::Error(CodigoError::NoAck, None),
+            Resultado::NoSoportado => Respuesta::Error(CodigoError::NotSupported, None),
+        }
+    }
+}
+
+impl<'a> From<&'a ResultadoPoll> for Respuesta<'a> {
+    fn from(resultado: &'a ResultadoPoll) -> Self {
+        match resultado {
+            ResultadoPoll::Dato(trama) => Respuesta::Dato(trama),
+            ResultadoPoll::Error(codigo) => Respuesta::Error(CodigoError::ScaleError, Some(codigo)),
+            ResultadoPoll::Timeout => Respuesta::Error(CodigoError::Timeout, None),
+            ResultadoPoll::NoEstable => Respuesta::Error(CodigoError::NotStable, None),
+        }
+    }
+}
+
+impl<'a> From<&'a ResultadoComando> for Respuesta<'a> {
+    fn from(resultado: &'a ResultadoComando) -> Self {
+        match resultado {
+            ResultadoComando::Cache(Some(trama)) => Respuesta::Dato(trama),
+            ResultadoComando::Cache(None) => Respuesta::Error(CodigoError::NoData, None),
+            ResultadoComando::Peso(resultado) => Respuesta::from(resultado),
+            ResultadoComando::Operacion(resultado) => Respuesta::from(resultado),
+        }
+    }
+}
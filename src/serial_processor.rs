@@ -1,21 +1,104 @@
-use crate::serial_utils::is_relevant_data;
-
-/// Ensambla mensajes del puerto serial terminados en 0x0D (carácter '\r').
-/// Devuelve `Some(Vec<u8>)` si el mensaje completo es relevante.
-/// Si el mensaje no es relevante, se descarta.
-pub fn ensamblar_y_filtrar_datos(buffer: &[u8], partial_data: &mut Vec<u8>) -> Option<Vec<u8>> {
-    // Acumular nuevos datos
-    partial_data.extend_from_slice(buffer);
-
-    // Buscar fin de mensaje
-    if let Some(pos) = partial_data.iter().position(|&b| b == 0x0D) {
-        let completo = partial_data.drain(..=pos).collect::<Vec<u8>>();
-        if is_relevant_data(&completo) {
-            return Some(completo);
+use std::time::Instant;
+
+use log::{debug, warn};
+
+use crate::filter::Filtro;
+use crate::framing::Framing;
+use crate::protocol::ScaleProtocol;
+use crate::serial_utils::sanitize_log_data;
+
+/// Tamaño máximo de datos parciales acumulados sin formar una trama.
+const MAX_PENDIENTE: usize = 4096;
+
+/// Ensambla mensajes del puerto serial según el framing configurado o, si no
+/// hay, el framing del protocolo (0x0D para el protocolo genérico),
+/// conservando los bytes sobrantes entre lecturas.
+pub struct Ensamblador {
+    partial_data: Vec<u8>,
+    ultimo_dato: Option<Instant>,
+    /// Trama cerrada por silencio justo antes de llegar bytes nuevos (`IdleGap`)
+    cerrada: Option<Vec<u8>>,
+}
+
+impl Ensamblador {
+    pub fn new() -> Self {
+        Self {
+            partial_data: Vec::new(),
+            ultimo_dato: None,
+            cerrada: None,
+        }
+    }
+
+    /// Acumula los bytes leídos y devuelve un iterador con todas las tramas
+    /// completas y relevantes que contienen. Las que rechaza el filtro
+    /// configurado o el protocolo se descartan.
+    pub fn ensamblar_y_filtrar_datos<'a>(
+        &'a mut self,
+        buffer: &[u8],
+        protocol: &'a dyn ScaleProtocol,
+        framing: Option<&'a Framing>,
+        filtro: &'a Filtro,
+    ) -> Tramas<'a> {
+        if !buffer.is_empty() {
+            if framing.map(|f| f.cerrada_por_silencio(&self.partial_data, self.ultimo_dato)).unwrap_or(false) {
+                self.cerrada = Some(std::mem::take(&mut self.partial_data));
+            }
+            self.partial_data.extend_from_slice(buffer);
+            self.ultimo_dato = Some(Instant::now());
+        }
+
+        if self.partial_data.len() > MAX_PENDIENTE {
+            let exceso = self.partial_data.len() - MAX_PENDIENTE;
+            warn!("⚠️ Buffer serial sin fin de trama, descartando {} bytes", exceso);
+            self.partial_data.drain(..exceso);
+        }
+
+        Tramas {
+            cerrada: self.cerrada.take(),
+            partial_data: &mut self.partial_data,
+            ultimo_dato: self.ultimo_dato,
+            protocol,
+            framing,
+            filtro,
         }
     }
 
-    None
+    /// Datos acumulados que aún no forman una trama completa
+    pub fn pendiente(&self) -> &[u8] {
+        &self.partial_data
+    }
 }
 
+impl Default for Ensamblador {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
+/// Iterador sobre las tramas completas disponibles tras una lectura.
+pub struct Tramas<'a> {
+    cerrada: Option<Vec<u8>>,
+    partial_data: &'a mut Vec<u8>,
+    ultimo_dato: Option<Instant>,
+    protocol: &'a dyn ScaleProtocol,
+    framing: Option<&'a Framing>,
+    filtro: &'a Filtro,
+}
+
+impl Iterator for Tramas<'_> {
+    type Item = Vec<u8>;
+
+    fn next(&mut self) -> Option<Vec<u8>> {
+        loop {
+            let completo = match (self.cerrada.take(), self.framing) {
+                (Some(cerrada), _) => cerrada,
+                (None, Some(framing)) => framing.next_frame(self.partial_data, self.ultimo_dato)?,
+                (None, None) => self.protocol.next_frame(self.partial_data)?,
+            };
+            if self.filtro.es_relevante(&completo) && self.protocol.is_relevant(&completo) {
+                return Some(completo);
+            }
+            debug!("🗑️ Trama descartada: {}", sanitize_log_data(&completo));
+        }
+    }
+}
@@ -1,34 +1,73 @@
 // === src/serial_reader.rs ===
 use std::io::{Write};
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use anyhow::Context;
 use flume::{Receiver, Selector};
 use log::{debug, info, warn};
+use parking_lot::RwLock;
 use serialport::SerialPort;
 
 use crate::cache::SharedCache;
-use crate::serial_processor::ensamblar_y_filtrar_datos;
+use crate::config::Config;
+use crate::filter::Filtro;
+use crate::protocol::ScaleProtocol;
+use crate::serial_processor::Ensamblador;
 use crate::serial_utils::sanitize_log_data;
+use crate::stats::Stats;
+
+/// Cada cuánto se registra en el log el resumen de contadores.
+const INTERVALO_RESUMEN: Duration = Duration::from_secs(60);
+/// Espera máxima de comandos entre dos lecturas del puerto
+const ESPERA_COMANDOS: Duration = Duration::from_millis(50);
 
 /// Inicia el hilo de lectura desde el puerto serial.
 pub fn start_serial_reader(
     mut serial: Box<dyn SerialPort>,
     cache: SharedCache,
     rx_serial_write: Receiver<Vec<u8>>,
+    protocol: Arc<dyn ScaleProtocol>,
+    config: Arc<RwLock<Config>>,
+    stats: Arc<Stats>,
 ) {
     thread::spawn(move || {
         let mut buffer = [0u8; 1024];
-        let mut partial_data = Vec::new();
+        let mut ensamblador = Ensamblador::new();
+        let reglas = config.read().filters.clone().unwrap_or_else(|| protocol.default_filters());
+        let mut filtro = Filtro::compilar(&reglas, stats.clone())
+            .expect("Reglas de filtro validadas al cargar la configuración");
+        let mut ultimo_resumen = Instant::now();
+        let mut resumen_anterior = Vec::new();
 
-        info!("🟡 Hilo de lectura serial iniciado. Esperando datos de la báscula...");
+        info!(
+            "🟡 Hilo de lectura serial iniciado (protocolo '{}'). Esperando datos de la báscula...",
+            protocol.name()
+        );
 
         loop {
-            // Esperar comandos del canal con timeout
+            if ultimo_resumen.elapsed() >= INTERVALO_RESUMEN {
+                let resumen = stats.snapshot();
+                if resumen != resumen_anterior {
+                    let texto: Vec<String> = resumen.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                    info!("📊 Contadores: {}", texto.join(", "));
+                    resumen_anterior = resumen;
+                }
+                ultimo_resumen = Instant::now();
+            }
+
+            // Esperar comandos del canal con timeout; con `idle_gap` se lee más seguido
+            // para que el silencio se mida con una fracción del intervalo configurado
+            let espera = config
+                .read()
+                .framing
+                .as_ref()
+                .and_then(|f| f.resolucion())
+                .map_or(ESPERA_COMANDOS, |r| r.min(ESPERA_COMANDOS));
             match Selector::new()
                 .recv(&rx_serial_write, |msg| msg)
-                .wait_timeout(Duration::from_millis(50))
+                .wait_timeout(espera)
             {
                 Ok(Ok(comando)) => {
                     if let Err(e) = serial
@@ -58,32 +97,98 @@ pub fn start_serial_reader(
             }
 
             // Leer datos del puerto serial
-            match serial.read(&mut buffer) {
-                Ok(bytes_read) if bytes_read > 0 => {
-                    let recibidos = &buffer[..bytes_read];
-                    debug!("📥 Bytes leídos (crudo): {}", sanitize_log_data(recibidos));
+            let bytes_read = match serial.read(&mut buffer) {
+                Ok(bytes_read) => bytes_read,
+                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
+                    // Timeout esperado, continuar
+                    0
+                }
+                Err(e) => {
+                    warn!("❌ Error al leer del puerto serial: {:?}", e);
+                    0
+                }
+            };
 
-                    match ensamblar_y_filtrar_datos(recibidos, &mut partial_data) {
-                        Some(msg) => {
-                            info!("✅ Dato completo de báscula recibido: {}", sanitize_log_data(&msg));
-                            cache.lock().set(msg);
-                        }
-                        None => {
-                            debug!("🧩 Fragmento acumulado: {}", sanitize_log_data(&partial_data));
+            // El framing y los filtros se releen en cada vuelta para respetar la recarga de configuración
+            let (framing, integridad) = {
+                let cfg = config.read();
+                let reglas = cfg.filters.clone().unwrap_or_else(|| protocol.default_filters());
+                if reglas.as_slice() != filtro.reglas() {
+                    match Filtro::compilar(&reglas, stats.clone()) {
+                        Ok(nuevo) => {
+                            info!("🔄 Reglas de filtro actualizadas ({})", reglas.len());
+                            filtro = nuevo;
                         }
+                        Err(e) => warn!("⚠️ Reglas de filtro inválidas, se mantienen las anteriores: {}", e),
                     }
                 }
-                Ok(_) => {
-                    // No se leyó nada
+                let integridad = cfg.integrity.clone().or_else(|| protocol.integrity());
+                (cfg.framing.clone(), integridad)
+            };
+            let por_tiempo = framing.as_ref().map(|f| f.depende_del_tiempo()).unwrap_or(false);
+            let hay_pendiente = por_tiempo && !ensamblador.pendiente().is_empty();
+            if bytes_read == 0 && !hay_pendiente {
+                continue;
+            }
+
+            let recibidos = &buffer[..bytes_read];
+            if !recibidos.is_empty() {
+                debug!("📥 Bytes leídos (crudo): {}", sanitize_log_data(recibidos));
+            }
+
+            // Procesar todas las tramas completas; a la caché va la más reciente
+            let mut ultima: Option<Vec<u8>> = None;
+            for msg in ensamblador.ensamblar_y_filtrar_datos(recibidos, protocol.as_ref(), framing.as_ref(), &filtro) {
+                if let Some(respuesta) = protocol.handshake_reply(&msg) {
+                    debug!("🤝 Handshake del protocolo: {}", sanitize_log_data(&respuesta));
+                    if let Err(e) = serial.write_all(&respuesta).and_then(|_| serial.flush()) {
+                        warn!("⚠️ Error al responder handshake: {}", e);
+                    }
+                    continue;
                 }
-                Err(ref e) if e.kind() == std::io::ErrorKind::TimedOut => {
-                    // Timeout esperado, continuar
+
+                // Las respuestas a comandos (ACK, ES, EL, NAK...) no pasan por la validación de
+                // integridad: ésta describe las tramas de peso (largo, checksum) y las
+                // respuestas ya se reconocen por su contenido exacto en `response`
+                if let Some(respuesta) = protocol.response(&msg) {
+                    info!("📨 Respuesta de báscula a comando: {:?}", respuesta);
+                    cache.set_response(respuesta);
+                    continue;
                 }
-                Err(e) => {
-                    warn!("❌ Error al leer del puerto serial: {:?}", e);
+
+                if let Some(Err(motivo)) = integridad.as_ref().map(|i| i.verificar(&msg)) {
+                    stats.incrementar(&format!("integridad.{}", motivo));
+                    warn!("🚫 Trama descartada por integridad ({}): {}", motivo, sanitize_log_data(&msg));
+                    continue;
                 }
+
+                info!("✅ Dato completo de báscula recibido: {}", sanitize_log_data(&msg));
+                ultima = Some(msg);
+            }
+
+            match ultima {
+                Some(msg) => {
+                    let lectura = protocol.parse(&msg);
+                    match &lectura {
+                        Some(l) => debug!(
+                            "⚖️ Lectura interpretada: {} {} {:?} estable={} rango={:?} tara={:?} cero={}",
+                            l.formatted_value(),
+                            l.unit,
+                            l.mode,
+                            l.stable,
+                            l.range,
+                            l.tare,
+                            l.at_zero
+                        ),
+                        None => debug!("❔ Trama sin peso reconocible"),
+                    }
+                    cache.set(msg, lectura);
+                }
+                None if !recibidos.is_empty() => {
+                    debug!("🧩 Fragmento acumulado: {}", sanitize_log_data(ensamblador.pendiente()));
+                }
+                None => {}
             }
         }
     });
 }
-
@@ -3,6 +3,9 @@ use serialport::{DataBits, Parity, StopBits};
 use anyhow::Result;
 // === src/serial_utils.rs ===
 use serde::{self, Deserialize, Deserializer};
+use std::collections::BTreeMap;
+
+use crate::command::{default_commands, CommandSpec};
 
 pub fn deserialize_data_bits<'de, D>(deserializer: D) -> Result<DataBits, D::Error>
 where
@@ -43,23 +46,34 @@ where
     }
 }
 
-
-/// Verifica si los datos recibidos son relevantes o deben descartarse.
-pub fn is_relevant_data(data: &[u8]) -> bool {
-    const IRRELEVANT_PATTERNS: &[&[u8]] = &[
-        &[0x18, 0x0D],
-        &[0x02, 0x3F, 0x58, 0x0D], // ?X
-        &[0x02, 0x3F, 0x50, 0x0D], // ?P
-        &[0x02, 0x3F, 0x44, 0x0D], // ?D
-        &[0x02, 0x3F, 0x41, 0x0D], // ?A
-        b"00000",
-    ];
-    const ENDS_WITH_PATTERN: &[u8] = b"0.005\r";
-    const CONTAINS_PATTERN: &[u8] = b"Count        Weight/kg";
-
-    !IRRELEVANT_PATTERNS.iter().any(|pat| data == *pat)
-        && !data.ends_with(ENDS_WITH_PATTERN)
-        && !data.windows(CONTAINS_PATTERN.len()).any(|w| w == CONTAINS_PATTERN)
+/// Lee la tabla `[commands]` y la combina con la tabla por defecto: los campos
+/// de una entrada con el mismo nombre (sin distinguir mayúsculas) reemplazan
+/// los de la entrada por defecto y los omitidos se conservan.
+pub fn deserialize_commands<'de, D>(deserializer: D) -> Result<BTreeMap<String, CommandSpec>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let propios = BTreeMap::<String, CommandSpec>::deserialize(deserializer)?;
+    let mut tabla = default_commands();
+    for (nombre, spec) in propios {
+        let nombre = nombre.trim().to_uppercase();
+        if nombre.is_empty() {
+            return Err(serde::de::Error::custom("nombre de comando vacío"));
+        }
+        let spec = match tabla.get(&nombre) {
+            Some(base) => spec.sobre(base),
+            None => spec,
+        };
+        // Sin `scale` el comando solo lee la caché: los bytes nunca se enviarían
+        if spec.serial.is_some() && spec.scale.is_none() {
+            return Err(serde::de::Error::custom(format!(
+                "el comando '{}' define serial pero no scale (p. ej. scale = \"poll\")",
+                nombre
+            )));
+        }
+        tabla.insert(nombre, spec);
+    }
+    Ok(tabla)
 }
 
 
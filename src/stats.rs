@@ -0,0 +1,24 @@
+use std::collections::BTreeMap;
+
+use parking_lot::Mutex;
+
+/// Contadores de eventos del puente (reglas de filtro, tramas descartadas...).
+#[derive(Default)]
+pub struct Stats {
+    contadores: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Stats {
+    pub fn incrementar(&self, clave: &str) {
+        *self.contadores.lock().entry(clave.to_string()).or_insert(0) += 1;
+    }
+
+    /// Copia de todos los contadores, ordenados por nombre
+    pub fn snapshot(&self) -> Vec<(String, u64)> {
+        self.contadores
+            .lock()
+            .iter()
+            .map(|(k, v)| (k.clone(), *v))
+            .collect()
+    }
+}
@@ -0,0 +1,147 @@
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use flume::{Receiver, TryRecvError};
+
+use crate::cache::{SharedCache, Trama};
+
+/// Qué tramas se envían a un suscriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModoSuscripcion {
+    /// Todas las tramas
+    Todas,
+    /// Solo cuando cambia el peso (o la trama, si no se pudo interpretar)
+    Cambio,
+    /// Solo lecturas estables
+    Estable,
+}
+
+/// Conexión TCP en modo push: recibe las tramas nuevas de la caché y las
+/// reenvía al cliente respetando el filtro y el intervalo mínimo.
+pub struct Suscripcion {
+    rx: Receiver<Trama>,
+    modo: ModoSuscripcion,
+    intervalo: Duration,
+    ultimo_envio: Option<Instant>,
+    /// Clave de la última trama enviada, para el modo `Cambio`
+    ultima_clave: Option<String>,
+    /// Trama más reciente retenida por el intervalo mínimo
+    pendiente: Option<Trama>,
+}
+
+/// Lo que distingue una trama de otra en el modo `Cambio`: el peso, o la
+/// trama cruda si no se pudo interpretar.
+fn clave(trama: &Trama) -> String {
+    match &trama.lectura {
+        Some(r) => format!("{} {}", r.formatted_value(), r.unit),
+        None => String::from_utf8_lossy(&trama.data).into_owned(),
+    }
+}
+
+impl Suscripcion {
+    /// Interpreta los argumentos de `SUBSCRIBE [CHANGE|STABLE] [interval_ms]`.
+    pub fn parse_args(args: &[&str]) -> Result<(ModoSuscripcion, Duration)> {
+        let mut modo = ModoSuscripcion::Todas;
+        let mut intervalo = Duration::ZERO;
+        for arg in args {
+            match arg.to_uppercase().as_str() {
+                "CHANGE" => modo = ModoSuscripcion::Cambio,
+                "STABLE" => modo = ModoSuscripcion::Estable,
+                "ALL" => modo = ModoSuscripcion::Todas,
+                otro => match otro.parse::<u64>() {
+                    Ok(ms) => intervalo = Duration::from_millis(ms),
+                    Err(_) => bail!("argumento de SUBSCRIBE inválido: '{}'", arg),
+                },
+            }
+        }
+        Ok((modo, intervalo))
+    }
+
+    pub fn nueva(cache: &SharedCache, modo: ModoSuscripcion, intervalo: Duration) -> Self {
+        Self {
+            rx: cache.lock().suscribir(),
+            modo,
+            intervalo,
+            ultimo_envio: None,
+            ultima_clave: None,
+            pendiente: None,
+        }
+    }
+
+    /// Trama que corresponde enviar ahora según el filtro y el intervalo mínimo, si la hay.
+    pub fn siguiente(&mut self) -> Result<Option<Trama>> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(trama) => {
+                    if self.modo == ModoSuscripcion::Estable && !trama.lectura.as_ref().map(|r| r.stable).unwrap_or(false) {
+                        continue;
+                    }
+                    // Se compara con lo último enviado, no con lo retenido: A→B→A dentro
+                    // del intervalo envía B, que el cliente todavía no vio
+                    if self.modo == ModoSuscripcion::Cambio && self.ultima_clave == Some(clave(&trama)) {
+                        continue;
+                    }
+                    self.pendiente = Some(trama);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => bail!("la caché cerró la suscripción"),
+            }
+        }
+
+        let a_tiempo = self.ultimo_envio.map(|t| t.elapsed() >= self.intervalo).unwrap_or(true);
+        match self.pendiente.take() {
+            Some(trama) if a_tiempo => {
+                self.ultimo_envio = Some(Instant::now());
+                if self.modo == ModoSuscripcion::Cambio {
+                    self.ultima_clave = Some(clave(&trama));
+                }
+                Ok(Some(trama))
+            }
+            retenida => {
+                self.pendiente = retenida;
+                Ok(None)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+    use crate::weight::parse_frame;
+
+    fn enviar(cache: &SharedCache, texto: &str) {
+        cache.set(texto.as_bytes().to_vec(), parse_frame(texto.as_bytes()));
+    }
+
+    fn siguiente(suscripcion: &mut Suscripcion) -> Option<String> {
+        suscripcion.siguiente().unwrap().map(|t| String::from_utf8(t.data).unwrap())
+    }
+
+    #[test]
+    fn cambio_compara_con_lo_enviado() {
+        let cache = SharedCache::default();
+        let intervalo = Duration::from_millis(50);
+        let mut suscripcion = Suscripcion::nueva(&cache, ModoSuscripcion::Cambio, intervalo);
+
+        enviar(&cache, "ST,GS,+  1.000kg\r");
+        assert_eq!(siguiente(&mut suscripcion).as_deref(), Some("ST,GS,+  1.000kg\r"));
+
+        // A→B→A dentro del intervalo: B queda retenida y A (ya enviada) se ignora
+        enviar(&cache, "ST,GS,+  2.000kg\r");
+        enviar(&cache, "ST,GS,+  1.000kg\r");
+        assert_eq!(siguiente(&mut suscripcion), None);
+        thread::sleep(intervalo);
+        assert_eq!(siguiente(&mut suscripcion).as_deref(), Some("ST,GS,+  2.000kg\r"));
+
+        // Volver a A después de enviar B sí es un cambio
+        thread::sleep(intervalo);
+        enviar(&cache, "ST,GS,+  1.000kg\r");
+        assert_eq!(siguiente(&mut suscripcion).as_deref(), Some("ST,GS,+  1.000kg\r"));
+        enviar(&cache, "ST,GS,+  1.000kg\r");
+        thread::sleep(intervalo);
+        assert_eq!(siguiente(&mut suscripcion), None);
+    }
+}
@@ -1,22 +1,21 @@
-use std::io::{Read, Write};
+use std::io::{ErrorKind, Read, Write};
 use std::net::{TcpListener, TcpStream};
 use std::thread;
-use std::time::{Duration, Instant};
-
-use flume::Sender;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use log::{info, warn};
 
 use crate::cache::SharedCache;
 use crate::config::RuntimeConfig;
-use crate::command::Comando;
+use crate::command::{Comando, LectorLineas, Linea};
+use crate::respuesta::{CodigoError, Estilo, Formato, Respuesta};
+use crate::protocol::ScaleProtocol;
+use crate::poll::{self, Coordinador};
+use crate::subscription::Suscripcion;
 
-/// Tipos de verificación sobre la caché
-enum CacheCheck {
-    ValidoDesdePasado(Duration),
-    PosteriorA(Instant, Duration),
-}
+/// Cada cuánto se revisan las tramas pendientes de una conexión suscrita
+const INTERVALO_SUSCRIPCION: Duration = Duration::from_millis(20);
 
 /// Inicia el servidor TCP y acepta conexiones entrantes.
 pub fn start_tcp_server(runtime_config: &RuntimeConfig, cache: SharedCache) {
@@ -25,15 +24,35 @@ pub fn start_tcp_server(runtime_config: &RuntimeConfig, cache: SharedCache) {
     }
 }
 
-/// Ejecuta el bucle principal del servidor TCP.
+/// Ejecuta el bucle principal del servidor TCP: escucha en `tcp_address` y en
+/// cada `[[listener]]` (las direcciones se toman al iniciar).
 fn run_server(runtime_config: &RuntimeConfig, cache: SharedCache) -> Result<()> {
     let config_guard = runtime_config.config.read();
-    let listener = TcpListener::bind(config_guard.address())
-        .context("No se pudo iniciar el servidor TCP")?;
-
-    info!("🟢 Servidor TCP escuchando en {}", config_guard.address());
+    let mut direcciones = vec![config_guard.address().to_string()];
+    direcciones.extend(config_guard.listeners.iter().map(|l| l.address.clone()));
     drop(config_guard);
 
+    let mut oyentes = Vec::new();
+    for direccion in direcciones {
+        let listener = TcpListener::bind(&direccion)
+            .with_context(|| format!("No se pudo iniciar el servidor TCP en {}", direccion))?;
+        info!("🟢 Servidor TCP escuchando en {}", direccion);
+        oyentes.push((listener, direccion));
+    }
+
+    let principal = oyentes.remove(0);
+    for (listener, direccion) in oyentes {
+        let runtime_config = runtime_config.clone();
+        let cache = cache.clone();
+        thread::spawn(move || aceptar_conexiones(listener, direccion, &runtime_config, cache));
+    }
+    aceptar_conexiones(principal.0, principal.1, runtime_config, cache);
+
+    Ok(())
+}
+
+/// Acepta conexiones en un puerto y atiende cada una en su propio hilo.
+fn aceptar_conexiones(listener: TcpListener, direccion: String, runtime_config: &RuntimeConfig, cache: SharedCache) {
     for stream in listener.incoming() {
         match stream {
             Ok(stream) => {
@@ -41,14 +60,16 @@ fn run_server(runtime_config: &RuntimeConfig, cache: SharedCache) -> Result<()>
                     .peer_addr()
                     .map(|a| a.to_string())
                     .unwrap_or_else(|_| "desconocido".to_string());
-                info!("🔌 Nueva conexión desde {}", peer);
+                info!("🔌 Nueva conexión desde {} en {}", peer, direccion);
 
                 let cache = cache.clone();
                 let config = runtime_config.config.clone();
-                let sender = runtime_config.serial_write_sender.clone();
+                let coordinador = runtime_config.coordinador.clone();
+                let protocol = runtime_config.protocol.clone();
+                let direccion = direccion.clone();
 
                 thread::spawn(move || {
-                    if let Err(e) = handle_client(stream, config, sender, cache) {
+                    if let Err(e) = handle_client(stream, direccion, config, coordinador, protocol, cache) {
                         warn!("❌ Error manejando cliente: {:?}", e);
                     }
                 });
@@ -56,52 +77,121 @@ fn run_server(runtime_config: &RuntimeConfig, cache: SharedCache) -> Result<()>
             Err(e) => warn!("⚠️ Error al aceptar conexión: {}", e),
         }
     }
-
-    Ok(())
 }
 
 /// Maneja una conexión con un cliente.
 fn handle_client(
     mut stream: TcpStream,
+    direccion: String,
     config: std::sync::Arc<parking_lot::RwLock<crate::config::Config>>,
-    sender: Sender<Vec<u8>>,
+    coordinador: std::sync::Arc<Coordinador>,
+    protocol: std::sync::Arc<dyn ScaleProtocol>,
     cache: SharedCache,
 ) -> Result<()> {
     let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
     let mut buffer = [0u8; 1024];
+    let mut lector = LectorLineas::default();
+    let mut suscripcion: Option<Suscripcion> = None;
+    let mut formato = Formato::default();
 
     loop {
+        // Los bytes se arman con el lock de la configuración y se escriben sin él,
+        // para que un cliente lento no frene la recarga ni a los demás hilos
+        if let Some(trama) = suscripcion.as_mut().map(|s| s.siguiente()).transpose()?.flatten() {
+            let bytes = con_estilo(&config, &direccion, formato, |estilo| Respuesta::Dato(&trama).bytes(estilo));
+            stream.write_all(&bytes).context("Error al enviar trama al suscriptor")?;
+        }
+
         let bytes_read = match stream.read(&mut buffer) {
             Ok(0) => {
                 info!("🔌 Cliente desconectado [{}]", peer);
                 break;
             }
             Ok(n) => n,
+            // Suscrito: el timeout de lectura solo sirve para despachar tramas
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
             Err(e) => {
                 warn!("⚠️ Error al leer del cliente [{}]: {}", peer, e);
                 break;
             }
         };
 
-        let comando_str = String::from_utf8_lossy(&buffer[..bytes_read]).trim().to_string();
-        info!("📥 Comando recibido del cliente [{}]: '{}'", peer, comando_str);
-
-        match Comando::parse(&comando_str) {
-            Some(Comando::Uno) => {
-                let config_guard = config.read();
-                responder_con_cache(
-                    &mut stream,
-                    &cache,
-                    CacheCheck::ValidoDesdePasado(Duration::from_millis(config_guard.cache_duration_ms)),
-                    b"NO DATA\n",
-                )?;
-            }
-            Some(Comando::W) => {
-                manejar_comando_w(&mut stream, &config, &sender, &cache, &peer)?;
+        // Procesar en orden todas las líneas completas recibidas
+        lector.agregar(&buffer[..bytes_read]);
+        while let Some(linea) = lector.siguiente() {
+            let mut salida = Salida { stream: &mut stream, formato, direccion: &direccion, config: &config };
+            let comando_str = match linea {
+                Linea::Texto(texto) => texto,
+                Linea::Excedida => {
+                    warn!("⚠️ Línea demasiado larga del cliente [{}], descartada", peer);
+                    salida.enviar(Respuesta::Error(CodigoError::InvalidCommand, None))?;
+                    continue;
+                }
+            };
+            info!("📥 Comando recibido del cliente [{}]: '{}'", peer, comando_str);
+
+            let palabras: Vec<&str> = comando_str.split_whitespace().collect();
+            match palabras[0].to_uppercase().as_str() {
+                "FORMAT" => {
+                    match palabras.get(1).and_then(|a| Formato::parse(a)).filter(|_| palabras.len() == 2) {
+                        Some(nuevo) => {
+                            formato = nuevo;
+                            info!("🧾 Cliente [{}] usa formato {:?}", peer, formato);
+                            salida.formato = formato;
+                            salida.enviar(Respuesta::Hecho("OK"))?;
+                        }
+                        None => {
+                            warn!("⚠️ Argumentos de FORMAT inválidos del cliente [{}]: '{}'", peer, comando_str);
+                            salida.enviar(Respuesta::Error(CodigoError::InvalidCommand, None))?;
+                        }
+                    }
+                    continue;
+                }
+                "SUBSCRIBE" => {
+                    match Suscripcion::parse_args(&palabras[1..]) {
+                        Ok((modo, intervalo)) => {
+                            suscripcion = Some(Suscripcion::nueva(&cache, modo, intervalo));
+                            salida.stream.set_read_timeout(Some(INTERVALO_SUSCRIPCION))?;
+                            info!(
+                                "📻 Cliente [{}] suscrito ({:?}, intervalo {:?}); suscriptores: {}",
+                                peer,
+                                modo,
+                                intervalo,
+                                cache.lock().suscriptores()
+                            );
+                            salida.enviar(Respuesta::Hecho("OK"))?;
+                        }
+                        Err(e) => {
+                            warn!("⚠️ {} [{}]", e, peer);
+                            salida.enviar(Respuesta::Error(CodigoError::InvalidCommand, None))?;
+                        }
+                    }
+                    continue;
+                }
+                "UNSUBSCRIBE" => {
+                    // Al soltar el receptor la caché descarta al suscriptor en la próxima trama
+                    suscripcion = None;
+                    salida.stream.set_read_timeout(None)?;
+                    info!("📴 Cliente [{}] canceló la suscripción", peer);
+                    salida.enviar(Respuesta::Hecho("OK"))?;
+                    continue;
+                }
+                _ => {}
             }
-            None => {
-                warn!("⚠️ Comando no reconocido del cliente [{}]: '{}'", peer, comando_str);
-                let _ = stream.write_all(b"Comando invalido\n");
+
+            let (comando, intervalo_minimo) = {
+                let config = config.read();
+                (Comando::parse(&comando_str, &config), Duration::from_millis(config.min_poll_interval_ms))
+            };
+            match comando {
+                Some(comando) => {
+                    let resultado = poll::ejecutar(&coordinador, protocol.as_ref(), &cache, &comando, intervalo_minimo)?;
+                    salida.enviar(Respuesta::from(&resultado))?;
+                }
+                None => {
+                    warn!("⚠️ Comando no reconocido del cliente [{}]: '{}'", peer, comando_str);
+                    salida.enviar(Respuesta::Error(CodigoError::InvalidCommand, None))?;
+                }
             }
         }
     }
@@ -109,110 +199,36 @@ fn handle_client(
     Ok(())
 }
 
-/// Envía al cliente el dato de la caché si cumple con el criterio, o un mensaje alternativo si no lo hace.
-fn responder_con_cache(
-    stream: &mut TcpStream,
-    cache: &SharedCache,
-    criterio: CacheCheck,
-    no_data_msg: &[u8],
-) -> Result<()> {
-    let guard = cache.lock();
-    let resultado = match criterio {
-        CacheCheck::ValidoDesdePasado(duracion) => {
-            guard
-                .get_raw()
-                .filter(|(_, t)| t.elapsed() <= duracion)
-                .map(|(data, _)| data)
-        }
-        CacheCheck::PosteriorA(start, ventana) => {
-            guard
-                .get_raw()
-                .filter(|(_, t)| *t >= start && *t <= start + ventana)
-                .map(|(data, _)| data)
-        }
-    };
-
-    match resultado {
-        Some(data) => {
-            stream.write_all(&data).context("Error al enviar datos al cliente")?;
-            let texto = String::from_utf8_lossy(&data);
-            info!("✅ Dato enviado al cliente: {}", texto.trim_end());
-        }
-        None => {
-            warn!("⚠️ No se encontró dato válido en caché según el criterio.");
-            let _ = stream.write_all(no_data_msg);
-        }
-    }
-
-    Ok(())
+/// Arma el estilo de respuesta de una conexión con la configuración vigente.
+/// `f` corre con el lock de lectura tomado: no debe escribir en el socket.
+fn con_estilo<R>(
+    config: &parking_lot::RwLock<crate::config::Config>,
+    direccion: &str,
+    formato: Formato,
+    f: impl FnOnce(&Estilo) -> R,
+) -> R {
+    let config = config.read();
+    let plantillas = config.plantillas(direccion);
+    f(&Estilo { formato, scale_id: &config.scale_id, plantillas: &plantillas })
 }
 
-/// Maneja el comando 'W' bajo la lógica propuesta.
-fn manejar_comando_w(
-    stream: &mut TcpStream,
-    config: &std::sync::Arc<parking_lot::RwLock<crate::config::Config>>,
-    sender: &Sender<Vec<u8>>,
-    cache: &SharedCache,
-    _peer: &str,
-) -> Result<()> {
-    let (w_duration_ms, w_response_timeout_ms) = {
-        let c = config.read();
-        (c.w_duration_ms, c.w_response_timeout_ms)
-    };
-
-    let w_duration = Duration::from_millis(w_duration_ms);
-    let timeout = Duration::from_millis(w_response_timeout_ms);
-
-    // Paso 1: Intentar usar caché reciente
-    responder_con_cache(
-        stream,
-        cache,
-        CacheCheck::ValidoDesdePasado(w_duration),
-        b"",
-    )?;
-
-    {
-        let guard = cache.lock();
-        if let Some((_, t)) = guard.get_raw() {
-            if t.elapsed() <= w_duration {
-                return Ok(()); // ya se envió dato válido
-            }
-        }
-    }
-
-    // Paso 2: Solicitar dato nuevo
-    info!("📤 Cache inválida/vencida. Enviando 'W' a la báscula...");
-    sender.send(b"W".to_vec()).context("Error enviando 'W' al serial")?;
-
-    let inicio = Instant::now();
-    let mut intentos = 0;
-    let max_intentos = timeout.as_millis() / 10;
-
-    loop {
-        responder_con_cache(
-            stream,
-            cache,
-            CacheCheck::PosteriorA(inicio, timeout),
-            b"",
-        )?;
-
-        {
-            let guard = cache.lock();
-            if let Some((_, t)) = guard.get_raw() {
-                if t >= inicio && t <= inicio + timeout {
-                    return Ok(()); // se envió respuesta reciente
-                }
-            }
-        }
+/// Conexión de un cliente junto con el formato en que se le responde.
+struct Salida<'a> {
+    stream: &'a mut TcpStream,
+    formato: Formato,
+    /// Dirección donde se aceptó la conexión, que elige las plantillas
+    direccion: &'a str,
+    config: &'a parking_lot::RwLock<crate::config::Config>,
+}
 
-        intentos += 1;
-        if intentos >= max_intentos {
-            warn!("⏱️ Timeout esperando nuevo dato luego de 'W'");
-            let _ = stream.write_all(b"W_TIMEOUT\n");
-            return Ok(());
+impl Salida<'_> {
+    /// Escribe una respuesta con el estilo de la conexión.
+    fn enviar(&mut self, respuesta: Respuesta) -> Result<()> {
+        let bytes = con_estilo(self.config, self.direccion, self.formato, |estilo| respuesta.bytes(estilo));
+        self.stream.write_all(&bytes).context("Error al enviar respuesta al cliente")?;
+        if let Respuesta::Dato(trama) = respuesta {
+            info!("✅ Dato enviado al cliente: {}", String::from_utf8_lossy(&trama.data).trim_end());
         }
-
-        thread::sleep(Duration::from_millis(10));
+        Ok(())
     }
 }
-
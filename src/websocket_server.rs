@@ -0,0 +1,178 @@
+use std::io::ErrorKind;
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use tungstenite::handshake::server::{Request, Response};
+use tungstenite::error::ProtocolError;
+use tungstenite::{Error as WsError, Message};
+
+use crate::cache::SharedCache;
+use crate::command::Comando;
+use crate::config::RuntimeConfig;
+use crate::poll;
+use crate::respuesta::{CodigoError, Respuesta};
+use crate::subscription::{ModoSuscripcion, Suscripcion};
+
+/// Cada cuánto se revisan las tramas pendientes y los mensajes del navegador
+const INTERVALO_WS: Duration = Duration::from_millis(20);
+/// Tiempo máximo para completar el handshake; sin él, una conexión TCP que
+/// nunca envía el pedido HTTP retendría su hilo para siempre
+const ESPERA_HANDSHAKE: Duration = Duration::from_secs(5);
+
+/// Inicia el servidor WebSocket en un hilo propio si `websocket_address` está configurada.
+pub fn start_websocket_server(runtime_config: &RuntimeConfig, cache: SharedCache) {
+    let address = match runtime_config.config.read().websocket_address.clone() {
+        Some(address) => address,
+        None => return,
+    };
+
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("❌ No se pudo iniciar el servidor WebSocket en {}: {}", address, e);
+            return;
+        }
+    };
+    info!("🟢 Servidor WebSocket escuchando en {}", address);
+
+    let runtime_config = runtime_config.clone();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let runtime_config = runtime_config.clone();
+                    let cache = cache.clone();
+                    thread::spawn(move || {
+                        if let Err(e) = handle_client(stream, &runtime_config, &cache) {
+                            warn!("❌ Error manejando cliente WebSocket: {:?}", e);
+                        }
+                    });
+                }
+                Err(e) => warn!("⚠️ Error al aceptar conexión WebSocket: {}", e),
+            }
+        }
+    });
+}
+
+/// Atiende un navegador: le envía cada trama nueva que pasa su filtro y
+/// ejecuta los comandos que manda por el mismo socket.
+fn handle_client(stream: TcpStream, runtime_config: &RuntimeConfig, cache: &SharedCache) -> Result<()> {
+    let peer = stream.peer_addr().map(|a| a.to_string()).unwrap_or_default();
+    stream.set_read_timeout(Some(ESPERA_HANDSHAKE))?;
+
+    // El filtro inicial va en la URL: `ws://host:puerto/?mode=change&interval=200`
+    let mut consulta = String::new();
+    // El tipo de error del callback lo impone tungstenite
+    #[allow(clippy::result_large_err)]
+    let mut socket = tungstenite::accept_hdr(stream, |request: &Request, response: Response| {
+        consulta = request.uri().query().unwrap_or_default().to_string();
+        Ok(response)
+    })
+    .map_err(|e| anyhow!("Error en el handshake WebSocket: {}", e))?;
+
+    let (modo, intervalo) = match parse_consulta(&consulta) {
+        Ok(filtro) => filtro,
+        Err(e) => {
+            warn!("⚠️ {} [{}]", e, peer);
+            let _ = socket.send(mensaje(runtime_config, &Respuesta::Error(CodigoError::InvalidCommand, None)));
+            let _ = socket.close(None);
+            return Ok(());
+        }
+    };
+    let mut suscripcion = Suscripcion::nueva(cache, modo, intervalo);
+    socket.get_ref().set_read_timeout(Some(INTERVALO_WS))?;
+    info!("🔌 Navegador conectado por WebSocket [{}] ({:?}, intervalo {:?})", peer, modo, intervalo);
+
+    loop {
+        if let Some(trama) = suscripcion.siguiente()? {
+            socket.send(mensaje(runtime_config, &Respuesta::Dato(&trama)))?;
+        }
+
+        match socket.read() {
+            Ok(Message::Text(texto)) => {
+                info!("📥 Comando WebSocket [{}]: '{}'", peer, texto.trim());
+                let respuesta = atender_comando(texto.trim(), runtime_config, cache, &mut suscripcion)?;
+                socket.send(respuesta)?;
+            }
+            Ok(_) => {}
+            // Sin mensajes del navegador: enviar los pong pendientes y seguir despachando
+            Err(WsError::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                match socket.flush() {
+                    Err(WsError::Io(e)) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+                    otro => otro?,
+                }
+            }
+            Err(
+                WsError::ConnectionClosed
+                | WsError::AlreadyClosed
+                | WsError::Protocol(ProtocolError::ResetWithoutClosingHandshake),
+            ) => {
+                info!("🔌 Navegador desconectado [{}]", peer);
+                break;
+            }
+            Err(e) => {
+                warn!("⚠️ Error al leer del navegador [{}]: {}", peer, e);
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Interpreta `mode=all|change|stable` e `interval=<ms>` de la URL de conexión.
+fn parse_consulta(consulta: &str) -> Result<(ModoSuscripcion, Duration)> {
+    let args: Vec<&str> = consulta
+        .split('&')
+        .filter_map(|par| par.split_once('='))
+        .filter(|(clave, _)| matches!(*clave, "mode" | "interval"))
+        .map(|(_, valor)| valor)
+        .collect();
+    Suscripcion::parse_args(&args)
+}
+
+/// Ejecuta un comando del navegador: `SUBSCRIBE` cambia el filtro; los demás
+/// son los de la tabla `[commands]`, como por TCP.
+fn atender_comando(
+    texto: &str,
+    runtime_config: &RuntimeConfig,
+    cache: &SharedCache,
+    suscripcion: &mut Suscripcion,
+) -> Result<Message> {
+    let palabras: Vec<&str> = texto.split_whitespace().collect();
+    if palabras.first().map(|p| p.eq_ignore_ascii_case("SUBSCRIBE")).unwrap_or(false) {
+        return Ok(match Suscripcion::parse_args(&palabras[1..]) {
+            Ok((modo, intervalo)) => {
+                *suscripcion = Suscripcion::nueva(cache, modo, intervalo);
+                mensaje(runtime_config, &Respuesta::Hecho("OK"))
+            }
+            Err(_) => mensaje(runtime_config, &Respuesta::Error(CodigoError::InvalidCommand, None)),
+        });
+    }
+
+    let (comando, intervalo_minimo) = {
+        let config = runtime_config.config.read();
+        (Comando::parse(texto, &config), Duration::from_millis(config.min_poll_interval_ms))
+    };
+    let comando = match comando {
+        Some(comando) => comando,
+        None => return Ok(mensaje(runtime_config, &Respuesta::Error(CodigoError::InvalidCommand, None))),
+    };
+
+    let resultado = poll::ejecutar(
+        &runtime_config.coordinador,
+        runtime_config.protocol.as_ref(),
+        cache,
+        &comando,
+        intervalo_minimo,
+    )?;
+    Ok(mensaje(runtime_config, &Respuesta::from(&resultado)))
+}
+
+/// Mensaje de texto con la respuesta en JSON.
+fn mensaje(runtime_config: &RuntimeConfig, respuesta: &Respuesta) -> Message {
+    Message::Text(respuesta.json(&runtime_config.config.read().scale_id))
+}
@@ -0,0 +1,147 @@
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Indicador bruto / neto / tara reportado por la báscula.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WeightMode {
+    Gross,
+    Net,
+    Tare,
+}
+
+/// Estado de rango del peso (sobrecarga / bajo cero).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeStatus {
+    Normal,
+    Overload,
+    Underload,
+}
+
+/// Lectura de peso estructurada obtenida a partir de una trama cruda.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightReading {
+    /// Valor numérico con signo
+    pub value: f64,
+    /// Cantidad de decimales con que la báscula reportó el valor
+    pub decimals: u8,
+    pub unit: String,
+    pub negative: bool,
+    pub mode: WeightMode,
+    pub stable: bool,
+    pub range: RangeStatus,
+    /// Tara informada por la báscula, si el protocolo la incluye
+    pub tare: Option<f64>,
+    /// La báscula indica centro de cero
+    pub at_zero: bool,
+}
+
+impl WeightReading {
+    /// Valor formateado con los mismos decimales que envió la báscula.
+    pub fn formatted_value(&self) -> String {
+        let signo = if self.negative { "-" } else { "" };
+        format!("{}{:.*}", signo, self.decimals as usize, self.value.abs())
+    }
+}
+
+static RE_PESO: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?i)(?P<signo>[+-])?\s*(?P<num>\d+(?:[.,]\d*)?|[.,]\d+)\s*(?P<unidad>kg|lbs|lb|oz|g|t)?\b")
+        .unwrap()
+});
+
+const UNIDADES: &[&str] = &["kg", "lbs", "lb", "oz", "g", "t"];
+/// Indicadores de estado de las tramas ASCII: peso estable / en movimiento
+const ESTABLE: &[&str] = &["ST", "STABLE"];
+const MOVIMIENTO: &[&str] = &["US", "M", "MOTION", "D"];
+
+/// Indica si una trama ASCII genérica trae un indicador de estable o en movimiento.
+pub fn informa_movimiento(data: &[u8]) -> bool {
+    String::from_utf8_lossy(data)
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .map(|t| t.to_uppercase())
+        .any(|t| ESTABLE.contains(&t.as_str()) || MOVIMIENTO.contains(&t.as_str()))
+}
+
+/// Interpreta una trama ASCII genérica (p. ej. `ST,GS,+  12.345kg\r`).
+/// Devuelve `None` si la trama no contiene un peso ni un estado de rango reconocible.
+pub fn parse_frame(data: &[u8]) -> Option<WeightReading> {
+    let texto: String = data
+        .iter()
+        .filter(|b| b.is_ascii_graphic() || **b == b' ')
+        .map(|&b| b as char)
+        .collect();
+
+    let range = detectar_rango(&texto);
+    let captura = RE_PESO.captures(&texto);
+
+    let (value, decimals, negative, mut unit, resto) = match &captura {
+        Some(c) => {
+            let num = c.name("num").map(|m| m.as_str()).unwrap_or_default().replace(',', ".");
+            let decimals = num.split('.').nth(1).map(|d| d.len() as u8).unwrap_or(0);
+            let negative = c.name("signo").map(|m| m.as_str() == "-").unwrap_or(false);
+            let absoluto: f64 = num.parse().ok()?;
+            let unit = c.name("unidad").map(|m| m.as_str().to_lowercase()).unwrap_or_default();
+            let total = c.get(0).unwrap();
+            let resto = format!("{} {}", &texto[..total.start()], &texto[total.end()..]);
+            (if negative { -absoluto } else { absoluto }, decimals, negative, unit, resto)
+        }
+        None if range != RangeStatus::Normal => (0.0, 0, false, String::new(), texto.clone()),
+        None => return None,
+    };
+
+    let tokens: Vec<String> = resto
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_uppercase())
+        .collect();
+
+    if unit.is_empty() {
+        if let Some(u) = tokens
+            .iter()
+            .map(|t| t.to_lowercase())
+            .find(|t| UNIDADES.contains(&t.as_str()) && t != "g" && t != "t")
+        {
+            unit = u;
+        }
+    }
+
+    let mode = tokens
+        .iter()
+        .find_map(|t| match t.as_str() {
+            "NT" | "N" | "NET" | "NETO" => Some(WeightMode::Net),
+            "TR" | "TA" | "PT" | "TARE" | "TARA" => Some(WeightMode::Tare),
+            "GS" | "G" | "GR" | "GROSS" | "BRUTO" => Some(WeightMode::Gross),
+            _ => None,
+        })
+        .unwrap_or(WeightMode::Gross);
+
+    let stable = !tokens.iter().any(|t| MOVIMIENTO.contains(&t.as_str())) && !texto.contains('?');
+
+    Some(WeightReading {
+        value,
+        decimals,
+        unit,
+        negative,
+        mode,
+        stable,
+        range,
+        tare: None,
+        at_zero: false,
+    })
+}
+
+fn detectar_rango(texto: &str) -> RangeStatus {
+    let mayus = texto.to_uppercase();
+    let tiene = |t: &str| {
+        mayus
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '-' && c != '+')
+            .any(|tok| tok == t)
+    };
+
+    if tiene("-OL") || tiene("UL") || tiene("UNDER") || tiene("UNDERLOAD") {
+        RangeStatus::Underload
+    } else if tiene("OL") || tiene("+OL") || tiene("OVER") || tiene("OVERLOAD") {
+        RangeStatus::Overload
+    } else {
+        RangeStatus::Normal
+    }
+}